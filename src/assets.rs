@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
+use bevy_kira_audio::AudioSource;
 use bevy_ninepatch::NinePatchBuilder;
 
 #[derive(Resource, AssetCollection)]
@@ -10,11 +11,19 @@ pub struct Images {
     pub locked: Handle<Image>,
     #[asset(path = "delete.png")]
     pub delete: Handle<Image>,
+    #[asset(path = "toggle.png")]
+    pub toggle: Handle<Image>,
+    #[asset(path = "copy.png")]
+    pub copy: Handle<Image>,
+    #[asset(path = "paste.png")]
+    pub paste: Handle<Image>,
 
     #[asset(path = "coin.png")]
     pub coin: Handle<Image>,
     #[asset(path = "spot.png")]
     pub spot: Handle<Image>,
+    #[asset(path = "cursor.png")]
+    pub cursor: Handle<Image>,
 
     #[asset(path = "conveyor-up.png")]
     pub conveyor_up: Handle<Image>,
@@ -42,6 +51,12 @@ pub struct Fonts {
     pub varela: Handle<Font>,
 }
 
+#[derive(Resource, AssetCollection)]
+pub struct Sounds {
+    #[asset(path = "reject.wav")]
+    pub reject: Handle<AudioSource>,
+}
+
 #[derive(Resource)]
 pub struct NinePatches {
     pub panel: Handle<NinePatchBuilder>,
@@ -58,8 +58,17 @@ pub fn run(app: &mut App) {
             );
     };
 
+    #[cfg(debug_assertions)]
+    if std::env::var("ONE_CLICKER_STRESS_COINS").is_ok() {
+        use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
+            .add_plugin(LogDiagnosticsPlugin::default());
+    }
+
     app.init_collection::<Images>()
         .init_collection::<Fonts>()
+        .init_collection::<Sounds>()
         .init_resource::<NinePatches>()
         .add_plugin(GamePlugin)
         .run();
@@ -0,0 +1,361 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    ui::FocusPolicy,
+};
+use bevy_ninepatch::{NinePatchBundle, NinePatchData};
+
+use crate::{
+    assets::{Fonts, Images, NinePatches},
+    palette,
+};
+
+use super::{
+    components::{Balance, Income},
+    hud::IncomeButton,
+    minimap::color_bytes,
+};
+
+/// Seconds between samples of the balance and income.
+const SAMPLE_PERIOD: f32 = 1.0;
+/// Ten minutes of samples.
+const RECENT_SAMPLES: usize = 600;
+/// Every this many samples one is kept for longer, one per minute.
+const LONG_SAMPLE_EVERY: u64 = 60;
+/// Twelve hours of samples kept for longer.
+const LONG_SAMPLES: usize = 720;
+
+const GRAPH_WIDTH: u32 = 360;
+const GRAPH_HEIGHT: u32 = 120;
+
+pub fn setup_balance_graph(
+    mut commands: Commands,
+    images: Res<Images>,
+    fonts: Res<Fonts>,
+    ninepatches: Res<NinePatches>,
+    mut image_assets: ResMut<Assets<Image>>,
+) {
+    commands.insert_resource(BalanceHistory::default());
+
+    let image = image_assets.add(Image::new_fill(
+        Extent3d {
+            width: GRAPH_WIDTH,
+            height: GRAPH_HEIGHT,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &color_bytes(palette::OFF_WHITE),
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    let text_style = |color, font_size| TextStyle {
+        font: fonts.varela.clone(),
+        color,
+        font_size,
+    };
+
+    let content = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|content| {
+            content
+                .spawn(TextBundle {
+                    text: Text::from_section("", text_style(palette::DARK_BLUE, 24.0)),
+                    focus_policy: FocusPolicy::Pass,
+                    ..default()
+                })
+                .insert(BalanceGraphTitle);
+
+            content
+                .spawn(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(GRAPH_WIDTH as f32), Val::Px(GRAPH_HEIGHT as f32)),
+                        margin: UiRect {
+                            top: Val::Px(8.0),
+                            bottom: Val::Px(8.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    image: image.clone().into(),
+                    ..default()
+                })
+                .insert(BalanceGraphImage);
+
+            content
+                .spawn(TextBundle {
+                    text: Text::from_sections([
+                        TextSection::new("", text_style(palette::BLUE, 20.0)),
+                        TextSection::new("", text_style(palette::ORANGE, 20.0)),
+                        TextSection::new(
+                            "\nClick the graph to switch between minutes and hours",
+                            text_style(palette::LIGHT_BROWN, 16.0),
+                        ),
+                    ]),
+                    focus_policy: FocusPolicy::Pass,
+                    ..default()
+                })
+                .insert(BalanceGraphLegend);
+        })
+        .insert(Name::new("Balance Graph Content"))
+        .id();
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(80.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .with_children(|panel| {
+            panel.spawn(NinePatchBundle {
+                nine_patch_data: NinePatchData::with_single_content(
+                    images.panel.clone(),
+                    ninepatches.panel.clone(),
+                    content,
+                ),
+                ..default()
+            });
+        })
+        .insert(Name::new("Balance Graph"))
+        .insert(BalanceGraphPanel);
+
+    commands.insert_resource(BalanceGraph {
+        open: false,
+        range: GraphRange::Minutes,
+        image,
+    });
+}
+
+pub fn record_balance_history(
+    time: Res<Time>,
+    balance: Res<Balance>,
+    income: Res<Income>,
+    mut history: ResMut<BalanceHistory>,
+) {
+    // Only new samples count as a change, so the graph is not redrawn every frame
+    history
+        .bypass_change_detection()
+        .sample_timer
+        .tick(time.delta());
+
+    if !history.sample_timer.just_finished() {
+        return;
+    }
+
+    let sample = BalanceSample {
+        balance: balance.coins as f64,
+        income: income.per_second(time.elapsed_seconds_f64()),
+    };
+
+    history.push(sample);
+}
+
+/// Clicking the income next to the balance opens and closes the graph,
+/// clicking the graph switches its range.
+pub fn toggle_balance_graph(
+    income_buttons: Query<&Interaction, (Changed<Interaction>, With<IncomeButton>)>,
+    graph_images: Query<&Interaction, (Changed<Interaction>, With<BalanceGraphImage>)>,
+    mut graph: ResMut<BalanceGraph>,
+) {
+    for interaction in income_buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            graph.open = !graph.open;
+        }
+    }
+
+    for interaction in graph_images.iter() {
+        if *interaction == Interaction::Clicked {
+            graph.range = match graph.range {
+                GraphRange::Minutes => GraphRange::Hours,
+                GraphRange::Hours => GraphRange::Minutes,
+            };
+        }
+    }
+}
+
+/// Draws the balance and the income as lines, each scaled to its own highest value.
+pub fn draw_balance_graph(
+    graph: Res<BalanceGraph>,
+    history: Res<BalanceHistory>,
+    mut image_assets: ResMut<Assets<Image>>,
+    mut panels: Query<&mut Visibility, With<BalanceGraphPanel>>,
+    mut titles: Query<&mut Text, (With<BalanceGraphTitle>, Without<BalanceGraphLegend>)>,
+    mut legends: Query<&mut Text, (With<BalanceGraphLegend>, Without<BalanceGraphTitle>)>,
+) {
+    for mut visibility in panels.iter_mut() {
+        if visibility.is_visible != graph.open {
+            visibility.is_visible = graph.open;
+        }
+    }
+
+    if !graph.open || !(graph.is_changed() || history.is_changed()) {
+        return;
+    }
+
+    let (samples, capacity, title) = match graph.range {
+        GraphRange::Minutes => (&history.recent, RECENT_SAMPLES, "Last 10 minutes"),
+        GraphRange::Hours => (&history.long, LONG_SAMPLES, "Last 12 hours"),
+    };
+
+    let max_balance = samples
+        .iter()
+        .map(|sample| sample.balance)
+        .fold(0.0, f64::max);
+    let max_income = samples
+        .iter()
+        .map(|sample| sample.income)
+        .fold(0.0, f64::max);
+
+    for mut text in titles.iter_mut() {
+        text.sections[0].value = title.to_string();
+    }
+
+    for mut text in legends.iter_mut() {
+        text.sections[0].value = format!("Balance up to {:.0}   ", max_balance);
+        text.sections[1].value = format!("Income up to {:.1}/s", max_income);
+    }
+
+    let image = if let Some(image) = image_assets.get_mut(&graph.image) {
+        image
+    } else {
+        return;
+    };
+
+    let mut pixels = vec![palette::OFF_WHITE; (GRAPH_WIDTH * GRAPH_HEIGHT) as usize];
+
+    plot(&mut pixels, samples, capacity, palette::BLUE, |sample| {
+        sample.balance / max_balance.max(1.0)
+    });
+    plot(&mut pixels, samples, capacity, palette::ORANGE, |sample| {
+        sample.income / max_income.max(1.0)
+    });
+
+    image.data = pixels.into_iter().flat_map(color_bytes).collect();
+}
+
+/// Draws a line through the samples, the newest at the right edge of the graph.
+/// Heights go from 0 at the bottom to 1 at the top.
+fn plot(
+    pixels: &mut [Color],
+    samples: &VecDeque<BalanceSample>,
+    capacity: usize,
+    color: Color,
+    height: impl Fn(&BalanceSample) -> f64,
+) {
+    let width = GRAPH_WIDTH as usize;
+    let top = (GRAPH_HEIGHT - 1) as f64;
+    let mut last_y = None;
+
+    for x in 0..width {
+        // Samples missing at the start of the history leave the left of the graph empty
+        let age = (width - 1 - x) * capacity / width;
+
+        let sample = match samples.len().checked_sub(age + 1) {
+            Some(index) => &samples[index],
+            None => continue,
+        };
+
+        let y = (top - height(sample).clamp(0.0, 1.0) * top).round() as usize;
+        let (from, to) = match last_y {
+            Some(last_y) => (y.min(last_y), y.max(last_y)),
+            None => (y, y),
+        };
+
+        // Two pixels thick, so flat lines stay visible
+        for y in from..=(to + 1).min(GRAPH_HEIGHT as usize - 1) {
+            pixels[y * width + x] = color;
+        }
+
+        last_y = Some(y);
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BalanceSample {
+    pub balance: f64,
+    pub income: f64,
+}
+
+/// Balance and income sampled every second, with a coarser history going back further.
+#[derive(Resource)]
+pub struct BalanceHistory {
+    recent: VecDeque<BalanceSample>,
+    long: VecDeque<BalanceSample>,
+    samples_taken: u64,
+    sample_timer: Timer,
+}
+
+impl Default for BalanceHistory {
+    fn default() -> Self {
+        BalanceHistory {
+            recent: VecDeque::with_capacity(RECENT_SAMPLES),
+            long: VecDeque::with_capacity(LONG_SAMPLES),
+            samples_taken: 0,
+            sample_timer: Timer::from_seconds(SAMPLE_PERIOD, TimerMode::Repeating),
+        }
+    }
+}
+
+impl BalanceHistory {
+    fn push(&mut self, sample: BalanceSample) {
+        if self.recent.len() == RECENT_SAMPLES {
+            self.recent.pop_front();
+        }
+
+        self.recent.push_back(sample);
+
+        if self.samples_taken.is_multiple_of(LONG_SAMPLE_EVERY) {
+            if self.long.len() == LONG_SAMPLES {
+                self.long.pop_front();
+            }
+
+            self.long.push_back(sample);
+        }
+
+        self.samples_taken += 1;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GraphRange {
+    Minutes,
+    Hours,
+}
+
+#[derive(Resource)]
+pub struct BalanceGraph {
+    pub open: bool,
+    pub range: GraphRange,
+    image: Handle<Image>,
+}
+
+#[derive(Component)]
+pub struct BalanceGraphPanel;
+
+#[derive(Component)]
+pub struct BalanceGraphImage;
+
+#[derive(Component)]
+pub struct BalanceGraphTitle;
+
+#[derive(Component)]
+pub struct BalanceGraphLegend;
@@ -0,0 +1,508 @@
+use bevy::{math::vec2, prelude::*};
+
+use crate::{
+    assets::{Fonts, Images},
+    palette,
+};
+
+use super::{
+    components::{Balance, Currency},
+    hud::{ToolGhost, ToolbarButtonCopy, ToolbarButtonPaste, ToolbarButtonSelectedEvent},
+    input::{WorldMouse, WorldMouseEvent},
+    input_map::{Action, InputMap},
+    machines::{Machine, MachinePlaceRequest, PlacedMachine},
+    selection::is_box_selecting,
+    settings::{read_text_file, write_text_file},
+    tile_tracked_entities::{TilePosition, TileTrackedEntities},
+    TILE_SIZE,
+};
+
+const BLUEPRINT_HEADER: &str = "OC1:";
+
+const BLUEPRINT_FILE: &str = "blueprint.txt";
+
+/// Farthest a machine can be from the blueprint origin, in tiles.
+/// Keeps pasted positions far from overflowing.
+const MAX_BLUEPRINT_OFFSET: i32 = 1024;
+
+pub fn ghost_select_blueprint(
+    mut world_mouse_events: EventReader<WorldMouseEvent>,
+    selection_ghosts: Query<&ToolbarButtonCopy, With<ToolGhost>>,
+    paste_buttons: Query<Entity, With<ToolbarButtonPaste>>,
+    machines: Query<(&Transform, &PlacedMachine)>,
+    mut clipboard: ResMut<BlueprintClipboard>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+) {
+    if selection_ghosts.is_empty() || is_box_selecting(&keys) {
+        return;
+    }
+
+    for event in world_mouse_events.iter() {
+        let (start_tile, end_tile) = match event {
+            WorldMouseEvent::Click { button, position }
+                if input_map.is_button(Action::Place, *button) =>
+            {
+                let tile = TilePosition::from_world(*position);
+                (tile, tile)
+            }
+
+            WorldMouseEvent::DragEnd {
+                button,
+                start_world,
+                end_world,
+            } if input_map.is_button(Action::Place, *button) => (
+                TilePosition::from_world(*start_world),
+                TilePosition::from_world(*end_world),
+            ),
+
+            _ => continue,
+        };
+
+        let blueprint = Blueprint::capture(start_tile, end_tile, machines.iter());
+
+        if blueprint.machines.is_empty() {
+            continue;
+        }
+
+        clipboard.blueprint = Some(blueprint);
+
+        if let Ok(paste_button) = paste_buttons.get_single() {
+            button_selected_events.send(ToolbarButtonSelectedEvent(Some(paste_button)));
+        }
+
+        break;
+    }
+}
+
+pub fn update_selection_ghost(
+    world_mouse: Res<WorldMouse>,
+    input_map: Res<InputMap>,
+    mut selection_ghosts: Query<(&mut Sprite, &mut Transform), With<SelectionRect>>,
+) {
+    let end_tile = TilePosition::from_world(world_mouse.position_world);
+    let start_tile = input_map
+        .drag_start(Action::Place, &world_mouse)
+        .map(TilePosition::from_world)
+        .unwrap_or(end_tile);
+
+    let size = vec2(
+        ((end_tile.x - start_tile.x).abs() + 1) as f32,
+        ((end_tile.y - start_tile.y).abs() + 1) as f32,
+    ) * TILE_SIZE;
+    let offset = (start_tile.to_vec() - end_tile.to_vec()) * TILE_SIZE / 2.0;
+
+    for (mut sprite, mut transform) in selection_ghosts.iter_mut() {
+        sprite.custom_size = Some(size);
+        transform.translation = offset.extend(transform.translation.z);
+    }
+}
+
+pub fn ghost_paste_blueprint(
+    mut world_mouse_events: EventReader<WorldMouseEvent>,
+    paste_ghosts: Query<&ToolbarButtonPaste, With<ToolGhost>>,
+    clipboard: Res<BlueprintClipboard>,
+    balance: Res<Balance>,
+    mut machine_place_requests: EventWriter<MachinePlaceRequest>,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+) {
+    if is_box_selecting(&keys) {
+        return;
+    }
+
+    let blueprint = match (paste_ghosts.get_single(), &clipboard.blueprint) {
+        (Ok(_), Some(blueprint)) => blueprint,
+        _ => return,
+    };
+
+    for event in world_mouse_events.iter() {
+        if let WorldMouseEvent::Click { button, position } = event {
+            if !input_map.is_button(Action::Place, *button) {
+                continue;
+            }
+
+            // Placing only the machines that are affordable would leave half a blueprint
+            if blueprint.cost() > balance.coins {
+                continue;
+            }
+
+            let origin = TilePosition::from_world(*position);
+
+            for entry in blueprint.machines.iter() {
+                machine_place_requests.send(MachinePlaceRequest {
+                    machine: entry.machine,
+                    position: origin.offset(entry.offset.x, entry.offset.y),
+                    history_cost: None,
+                    enabled: true,
+                });
+            }
+        }
+    }
+}
+
+pub fn update_blueprint_ghost(
+    world_mouse: Res<WorldMouse>,
+    balance: Res<Balance>,
+    clipboard: Res<BlueprintClipboard>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    machines: Query<&PlacedMachine>,
+    mut ghost_tiles: Query<(&mut Sprite, &BlueprintGhostTile)>,
+    mut cost_labels: Query<&mut Text, With<BlueprintCostLabel>>,
+) {
+    let blueprint = if let Some(blueprint) = &clipboard.blueprint {
+        blueprint
+    } else {
+        return;
+    };
+
+    let affordable = blueprint.cost() <= balance.coins;
+    let origin = TilePosition::from_world(world_mouse.position_world);
+
+    for (mut sprite, BlueprintGhostTile(offset)) in ghost_tiles.iter_mut() {
+        let position = origin.offset(offset.x, offset.y);
+
+        let occupied = tile_tracked_entities
+            .get_entities_in_tile(position)
+            .is_some_and(|entities| entities.iter().any(|entity| machines.contains(*entity)));
+
+        sprite.color = if occupied || !affordable {
+            palette::INVALID_GHOST
+        } else {
+            palette::VALID_GHOST
+        };
+    }
+
+    for mut text in cost_labels.iter_mut() {
+        text.sections[0].style.color = if affordable {
+            palette::DARK_BLUE
+        } else {
+            palette::ORANGE
+        };
+    }
+}
+
+pub fn export_blueprint(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    clipboard: Res<BlueprintClipboard>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if !(ctrl && input_map.just_pressed(Action::ExportBlueprint, &keys)) {
+        return;
+    }
+
+    if let Some(blueprint) = &clipboard.blueprint {
+        let encoded = blueprint.encode();
+
+        info!("Blueprint: {}", encoded);
+
+        if let Err(error) = write_text_file(BLUEPRINT_FILE, &encoded) {
+            warn!("Could not write {}: {}", BLUEPRINT_FILE, error);
+        }
+    }
+}
+
+pub fn import_blueprint(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    paste_buttons: Query<Entity, With<ToolbarButtonPaste>>,
+    mut clipboard: ResMut<BlueprintClipboard>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if !(ctrl && input_map.just_pressed(Action::ImportBlueprint, &keys)) {
+        return;
+    }
+
+    let encoded = match read_text_file(BLUEPRINT_FILE) {
+        Ok(encoded) => encoded,
+        Err(error) => {
+            warn!("Could not read {}: {}", BLUEPRINT_FILE, error);
+            return;
+        }
+    };
+
+    match Blueprint::decode(&encoded) {
+        Ok(blueprint) => {
+            clipboard.blueprint = Some(blueprint);
+
+            if let Ok(paste_button) = paste_buttons.get_single() {
+                button_selected_events.send(ToolbarButtonSelectedEvent(Some(paste_button)));
+            }
+        }
+
+        Err(error) => warn!("Could not import blueprint: {:?}", error),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlueprintMachine {
+    pub offset: TilePosition,
+    pub machine: Machine,
+}
+
+/// A group of machines with their positions relative to the blueprint origin.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Blueprint {
+    pub machines: Vec<BlueprintMachine>,
+}
+
+impl Blueprint {
+    /// Captures every placed machine in the rectangle spanned by the two tiles.
+    /// The origin of the blueprint is the center of the rectangle.
+    pub fn capture<'a>(
+        corner_a: TilePosition,
+        corner_b: TilePosition,
+        machines: impl Iterator<Item = (&'a Transform, &'a PlacedMachine)>,
+    ) -> Blueprint {
+        let min = TilePosition::new(corner_a.x.min(corner_b.x), corner_a.y.min(corner_b.y));
+        let max = TilePosition::new(corner_a.x.max(corner_b.x), corner_a.y.max(corner_b.y));
+        let origin =
+            TilePosition::new((min.x + max.x).div_euclid(2), (min.y + max.y).div_euclid(2));
+
+        let mut machines: Vec<_> = machines
+            .filter_map(|(transform, placed_machine)| {
+                let tile = TilePosition::from_world(transform.translation.truncate());
+
+                if tile.x < min.x || tile.x > max.x || tile.y < min.y || tile.y > max.y {
+                    return None;
+                }
+
+                Some(BlueprintMachine {
+                    offset: tile.offset(-origin.x, -origin.y),
+                    machine: placed_machine.machine,
+                })
+            })
+            .collect();
+
+        machines.sort_by_key(|entry| (entry.offset.y, entry.offset.x));
+
+        Blueprint { machines }
+    }
+
+    pub fn cost(&self) -> Currency {
+        self.machines.iter().map(|entry| entry.machine.cost()).sum()
+    }
+
+    /// Encodes the blueprint as `OC1:x,y,K;x,y,K;...` where `K` is [`Machine::code`].
+    pub fn encode(&self) -> String {
+        let entries: Vec<_> = self
+            .machines
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{},{},{}",
+                    entry.offset.x,
+                    entry.offset.y,
+                    entry.machine.code()
+                )
+            })
+            .collect();
+
+        format!("{}{}", BLUEPRINT_HEADER, entries.join(";"))
+    }
+
+    pub fn decode(encoded: &str) -> Result<Blueprint, BlueprintDecodeError> {
+        let body = encoded
+            .trim()
+            .strip_prefix(BLUEPRINT_HEADER)
+            .ok_or(BlueprintDecodeError::MissingHeader)?;
+
+        let mut machines: Vec<BlueprintMachine> = Vec::new();
+
+        for entry in body.split(';').filter(|entry| !entry.is_empty()) {
+            let invalid = || BlueprintDecodeError::InvalidEntry(entry.to_string());
+
+            let mut parts = entry.split(',');
+            let x = parts
+                .next()
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(invalid)?;
+            let y = parts
+                .next()
+                .and_then(|y| y.parse().ok())
+                .ok_or_else(invalid)?;
+            let machine = parts
+                .next()
+                .and_then(|code| {
+                    let mut chars = code.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(code), None) => Machine::from_code(code),
+                        _ => None,
+                    }
+                })
+                .ok_or_else(invalid)?;
+
+            if parts.next().is_some() {
+                return Err(invalid());
+            }
+
+            let in_range =
+                |offset: i32| (-MAX_BLUEPRINT_OFFSET..=MAX_BLUEPRINT_OFFSET).contains(&offset);
+
+            if !in_range(x) || !in_range(y) {
+                return Err(BlueprintDecodeError::OffsetOutOfRange(entry.to_string()));
+            }
+
+            let offset = TilePosition::new(x, y);
+
+            if machines.iter().any(|other| other.offset == offset) {
+                return Err(BlueprintDecodeError::DuplicateOffset(entry.to_string()));
+            }
+
+            machines.push(BlueprintMachine { offset, machine });
+        }
+
+        Ok(Blueprint { machines })
+    }
+
+    pub fn spawn_graphics(
+        &self,
+        commands: &mut Commands,
+        images: &Images,
+        fonts: &Fonts,
+    ) -> Entity {
+        let mut top = 0;
+        let mut left = 0;
+        let mut right = 0;
+
+        let ghost_tiles: Vec<_> = self
+            .machines
+            .iter()
+            .map(|entry| {
+                top = top.max(entry.offset.y);
+                left = left.min(entry.offset.x);
+                right = right.max(entry.offset.x);
+
+                let tile = entry.machine.spawn_graphics(commands, images, false);
+
+                commands
+                    .entity(tile)
+                    .insert(Transform::from_translation(
+                        (entry.offset.to_vec() * TILE_SIZE).extend(0.0),
+                    ))
+                    .insert(BlueprintGhostTile(entry.offset));
+
+                tile
+            })
+            .collect();
+
+        let cost_label = commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(
+                    self.cost().to_string(),
+                    TextStyle {
+                        font: fonts.varela.clone(),
+                        color: palette::DARK_BLUE,
+                        font_size: 96.0,
+                    },
+                )
+                .with_alignment(TextAlignment::BOTTOM_CENTER),
+                transform: Transform::from_xyz(
+                    (left + right) as f32 * TILE_SIZE / 2.0,
+                    (top as f32 + 0.5) * TILE_SIZE,
+                    0.1,
+                ),
+                ..default()
+            })
+            .insert(BlueprintCostLabel)
+            .id();
+
+        commands
+            .spawn(SpatialBundle::default())
+            .push_children(&ghost_tiles)
+            .add_child(cost_label)
+            .insert(Name::new("Blueprint Ghost"))
+            .id()
+    }
+}
+
+#[derive(Debug)]
+pub enum BlueprintDecodeError {
+    MissingHeader,
+    InvalidEntry(String),
+    DuplicateOffset(String),
+    OffsetOutOfRange(String),
+}
+
+#[derive(Resource, Default)]
+pub struct BlueprintClipboard {
+    pub blueprint: Option<Blueprint>,
+}
+
+#[derive(Component)]
+pub struct BlueprintGhostTile(pub TilePosition);
+
+#[derive(Component)]
+pub struct BlueprintCostLabel;
+
+#[derive(Component)]
+pub struct SelectionRect;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let blueprint = Blueprint {
+            machines: vec![
+                BlueprintMachine {
+                    offset: TilePosition::new(-1, -2),
+                    machine: Machine::Miner,
+                },
+                BlueprintMachine {
+                    offset: TilePosition::new(0, 0),
+                    machine: Machine::ConveyorRight,
+                },
+                BlueprintMachine {
+                    offset: TilePosition::new(3, 1),
+                    machine: Machine::Collector,
+                },
+            ],
+        };
+
+        let decoded = Blueprint::decode(&blueprint.encode()).unwrap();
+
+        assert_eq!(decoded, blueprint);
+    }
+
+    #[test]
+    fn empty_blueprint_roundtrip() {
+        let blueprint = Blueprint::default();
+
+        assert_eq!(Blueprint::decode(&blueprint.encode()).unwrap(), blueprint);
+    }
+
+    #[test]
+    fn decode_rejects_invalid_input() {
+        assert!(matches!(
+            Blueprint::decode("0,0,M"),
+            Err(BlueprintDecodeError::MissingHeader)
+        ));
+        assert!(matches!(
+            Blueprint::decode("OC1:0,0"),
+            Err(BlueprintDecodeError::InvalidEntry(_))
+        ));
+        assert!(matches!(
+            Blueprint::decode("OC1:0,0,M,1"),
+            Err(BlueprintDecodeError::InvalidEntry(_))
+        ));
+        assert!(matches!(
+            Blueprint::decode("OC1:1,2,M;1,2,C"),
+            Err(BlueprintDecodeError::DuplicateOffset(_))
+        ));
+        assert!(matches!(
+            Blueprint::decode("OC1:2147483647,0,M"),
+            Err(BlueprintDecodeError::OffsetOutOfRange(_))
+        ));
+        assert!(matches!(
+            Blueprint::decode("OC1:0,-2147483648,M"),
+            Err(BlueprintDecodeError::OffsetOutOfRange(_))
+        ));
+    }
+}
@@ -1,4 +1,7 @@
+use std::collections::VecDeque;
+
 use bevy::prelude::*;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 
 pub type Currency = u128;
 
@@ -13,6 +16,39 @@ impl Default for Balance {
     }
 }
 
+/// Seconds of credits the income rate is averaged over.
+const INCOME_WINDOW: f64 = 10.0;
+
+/// Recent credits to the balance, to tell how fast money comes in.
+#[derive(Resource, Default)]
+pub struct Income {
+    credits: VecDeque<(f64, Currency)>,
+}
+
+impl Income {
+    pub fn credit(&mut self, time: f64, coins: Currency) {
+        let cutoff = time - INCOME_WINDOW;
+
+        while matches!(self.credits.front(), Some((credit_time, _)) if *credit_time <= cutoff) {
+            self.credits.pop_front();
+        }
+
+        self.credits.push_back((time, coins));
+    }
+
+    /// Coins per second over the last few seconds.
+    pub fn per_second(&self, now: f64) -> f64 {
+        let coins = self
+            .credits
+            .iter()
+            .filter(|(credit_time, _)| *credit_time > now - INCOME_WINDOW)
+            .map(|(_, coins)| *coins as f64)
+            .sum::<f64>();
+
+        coins / INCOME_WINDOW
+    }
+}
+
 #[derive(Component)]
 pub struct Money(pub Currency);
 
@@ -30,6 +66,107 @@ pub struct Coin {
     pub alive: bool,
 }
 
+#[derive(Component)]
+pub struct CoinLabel;
+
+#[derive(Resource)]
+pub struct CoinLabels {
+    pub visible: bool,
+    pub max_zoom: f32,
+}
+
+impl Default for CoinLabels {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            max_zoom: 10.0,
+        }
+    }
+}
+
+/// Optional rule that merges resting coins sharing a tile into a single coin.
+#[derive(Resource)]
+pub struct CoinMerging {
+    pub enabled: bool,
+    pub timer: Timer,
+}
+
+impl Default for CoinMerging {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timer: Timer::from_seconds(0.5, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Pausing stops machines and coin movement, the player can still build and collect.
+#[derive(Resource, Default)]
+pub struct Simulation {
+    pub paused: bool,
+}
+
+/// Source of the random decisions of the simulation, seeded so that runs can be replayed.
+/// Reseeded whenever gameplay starts, the seed is what a recording stores of it.
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Uses the seed in `ONE_CLICKER_SEED` when set, so a run can be repeated.
+    pub fn from_env() -> Self {
+        let rng = match std::env::var("ONE_CLICKER_SEED").map(|seed| seed.parse()) {
+            Ok(Ok(seed)) => GameRng::new(seed),
+            Ok(Err(error)) => {
+                warn!("Ignoring invalid ONE_CLICKER_SEED: {}", error);
+                GameRng::default()
+            }
+            Err(_) => GameRng::default(),
+        };
+
+        info!("Game seed {}", rng.seed());
+
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng::new(rand::random())
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
 #[derive(Resource)]
 pub struct NextCoinDepth {
     pub depth: f32,
@@ -47,3 +184,122 @@ impl Coin {
         self.alive && self.spawn_timer.finished() && self.despawn_timer.paused()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use bevy::{asset::FileAssetIo, utils::Instant};
+    use bevy_asset_loader::prelude::*;
+
+    use super::*;
+    use crate::{
+        assets::{Fonts, Images},
+        gameplay::{
+            input::WorldMouseEvent,
+            input_map::InputMap,
+            machines::{act_machines, spew_velocity, Machine, PlacedMachine},
+            systems::{click_coins, click_velocity},
+            tile_tracked_entities::TileTrackedEntities,
+        },
+    };
+
+    /// Clicks and machine outputs in the order a short game makes them.
+    fn coin_velocities(rng: &mut GameRng) -> Vec<Vec2> {
+        let mut velocities = Vec::new();
+
+        for angle in [-PI / 2.0, PI / 2.0, PI, 0.0] {
+            velocities.push(click_velocity(rng));
+            velocities.push(spew_velocity(rng, angle));
+        }
+
+        velocities
+    }
+
+    #[test]
+    fn same_seed_gives_same_coins() {
+        let first = coin_velocities(&mut GameRng::new(42));
+        let second = coin_velocities(&mut GameRng::new(42));
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn different_seeds_give_different_coins() {
+        let first = coin_velocities(&mut GameRng::new(1));
+        let second = coin_velocities(&mut GameRng::new(2));
+
+        assert_ne!(first, second);
+    }
+
+    /// Clicks a few times next to running miners, the way the gameplay stage runs them,
+    /// and returns where the coins are and how they fly.
+    fn play(seed: u64) -> Vec<(Vec3, Vec2)> {
+        let mut world = World::new();
+
+        world.insert_resource(AssetServer::new(FileAssetIo::new("assets", false)));
+        world.init_resource::<DynamicAssets>();
+        let fonts = Fonts::create(&mut world);
+        let images = Images::create(&mut world);
+
+        let start = Instant::now();
+        let mut time = Time::new(start);
+        time.update_with_instant(start);
+
+        world.insert_resource(fonts);
+        world.insert_resource(images);
+        world.insert_resource(time);
+        world.insert_resource(GameRng::new(seed));
+        world.insert_resource(InputMap::default());
+        world.insert_resource(TileTrackedEntities::new());
+        world.insert_resource(NextCoinDepth {
+            depth: 0.1,
+            step: 0.00000001,
+        });
+        world.init_resource::<Events<WorldMouseEvent>>();
+        world.init_resource::<Events<CoinPickup>>();
+
+        for x in 0..3 {
+            world.spawn((
+                Transform::from_xyz(x as f32 * 64.0, 0.0, 0.0),
+                PlacedMachine::new(Machine::Miner),
+            ));
+        }
+
+        let mut stage = SystemStage::parallel();
+        stage
+            .add_system(click_coins)
+            .add_system(act_machines.after(click_coins));
+
+        for frame in 0..4 {
+            world.send_event(WorldMouseEvent::Click {
+                button: MouseButton::Left,
+                position: Vec2::new(frame as f32 * 10.0, 100.0),
+            });
+
+            // Every frame is long enough for the miners to act
+            let mut time = world.resource_mut::<Time>();
+            let last_update = time.last_update().unwrap();
+            time.update_with_instant(last_update + Machine::Miner.action_period());
+
+            stage.run(&mut world);
+        }
+
+        world
+            .query_filtered::<(&Transform, &Particle), With<Coin>>()
+            .iter(&world)
+            .map(|(transform, particle)| (transform.translation, particle.velocity))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_game() {
+        let first = play(42);
+        let second = play(42);
+
+        // Three miners and one click on each of the four frames
+        assert_eq!(first.len(), 16);
+        assert_eq!(first, second);
+        assert_ne!(first, play(43));
+    }
+}
@@ -0,0 +1,261 @@
+use bevy::{math::vec3, prelude::*, ui::FocusPolicy};
+
+use crate::assets::Images;
+
+use super::{
+    hud::{Toolbar, ToolbarButton, ToolbarButtonSelectedEvent},
+    input_map::{Action, InputMap},
+    settings::Settings,
+};
+
+/// Window pixels per second with the stick fully tilted.
+const CURSOR_SPEED: f32 = 900.0;
+/// World units per second at zoom 1 with the stick fully tilted.
+const PAN_SPEED: f32 = 900.0;
+const ZOOM_SPEED: f32 = 2.0;
+const STICK_DEADZONE: f32 = 0.15;
+const CURSOR_SIZE: f32 = 48.0;
+
+/// Gamepad buttons that act as the mouse buttons bound to these actions.
+const CURSOR_BUTTONS: [(GamepadButtonType, Action); 3] = [
+    (GamepadButtonType::South, Action::Place),
+    (GamepadButtonType::East, Action::Cancel),
+    (GamepadButtonType::West, Action::Pick),
+];
+
+pub fn setup_virtual_cursor(mut commands: Commands, images: Res<Images>) {
+    commands.insert_resource(VirtualCursor::default());
+
+    commands
+        .spawn(ImageBundle {
+            image: images.cursor.clone().into(),
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Px(CURSOR_SIZE), Val::Px(CURSOR_SIZE)),
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility { is_visible: false },
+            z_index: ZIndex::Global(5),
+            ..default()
+        })
+        .insert(Name::new("Virtual Cursor"))
+        .insert(VirtualCursorImage);
+}
+
+/// The left stick moves the cursor and face buttons press the mouse buttons of their actions.
+/// Using the gamepad takes over from the mouse, moving the mouse gives control back.
+pub fn update_virtual_cursor(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    input_map: Res<InputMap>,
+    windows: Res<Windows>,
+    time: Res<Time>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut virtual_cursor: ResMut<VirtualCursor>,
+) {
+    let window = if let Some(window) = windows.get_primary() {
+        window
+    } else {
+        return;
+    };
+
+    let window_size = Vec2::new(window.width(), window.height());
+
+    virtual_cursor.buttons.clear();
+
+    // Gestures started with the gamepad are finished with it
+    let mouse_moved = cursor_moved_events.iter().last().is_some();
+
+    if mouse_moved && virtual_cursor.buttons.get_pressed().len() == 0 {
+        virtual_cursor.active = false;
+    }
+
+    for gamepad in gamepads.iter() {
+        let movement = stick(
+            &axes,
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+        );
+        let mut used = movement != Vec2::ZERO;
+
+        for (button_type, action) in CURSOR_BUTTONS {
+            let button = if let Some(button) = input_map.button(action) {
+                button
+            } else {
+                continue;
+            };
+
+            let gamepad_button = GamepadButton::new(gamepad, button_type);
+
+            if gamepad_buttons.just_pressed(gamepad_button) {
+                virtual_cursor.buttons.press(button);
+                used = true;
+            }
+
+            if gamepad_buttons.just_released(gamepad_button) {
+                virtual_cursor.buttons.release(button);
+            }
+        }
+
+        if used && !virtual_cursor.active {
+            virtual_cursor.active = true;
+            virtual_cursor.position_window = window.cursor_position().unwrap_or(window_size / 2.0);
+        }
+
+        virtual_cursor.position_window = (virtual_cursor.position_window
+            + movement * CURSOR_SPEED * time.delta_seconds())
+        .clamp(Vec2::ZERO, window_size);
+    }
+}
+
+/// Shoulder buttons step through the enabled toolbar entries.
+pub fn cycle_tool_on_shoulders(
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    toolbars: Query<&Children, With<Toolbar>>,
+    buttons: Query<&ToolbarButton>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    let mut step = 0;
+
+    for gamepad in gamepads.iter() {
+        let just_pressed =
+            |button_type| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type));
+
+        if just_pressed(GamepadButtonType::LeftTrigger) {
+            step -= 1;
+        }
+
+        if just_pressed(GamepadButtonType::RightTrigger) {
+            step += 1;
+        }
+    }
+
+    if step == 0 {
+        return;
+    }
+
+    let toolbar_entries = if let Ok(toolbar_entries) = toolbars.get_single() {
+        toolbar_entries
+    } else {
+        return;
+    };
+
+    let entries = toolbar_entries
+        .iter()
+        .filter_map(|&entity| buttons.get(entity).ok().map(|button| (entity, button)))
+        .filter(|(_, button)| button.enabled)
+        .collect::<Vec<_>>();
+
+    if entries.is_empty() {
+        return;
+    }
+
+    let count = entries.len() as i32;
+    let next = match entries.iter().position(|(_, button)| button.selected) {
+        Some(index) => (index as i32 + step).rem_euclid(count),
+        None if step > 0 => 0,
+        None => count - 1,
+    };
+
+    button_selected_events.send(ToolbarButtonSelectedEvent(Some(entries[next as usize].0)));
+}
+
+/// The right stick pans, faster when zoomed out. The right trigger zooms in, the left one out.
+pub fn move_camera_with_gamepad(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    trigger_axes: Res<Axis<GamepadButton>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    let mut camera_transform = camera.single_mut();
+
+    for gamepad in gamepads.iter() {
+        let pan = stick(
+            &axes,
+            gamepad,
+            GamepadAxisType::RightStickX,
+            GamepadAxisType::RightStickY,
+        );
+
+        let scale = camera_transform.scale.x;
+        camera_transform.translation +=
+            (pan * PAN_SPEED * scale * time.delta_seconds()).extend(0.0);
+
+        let trigger = |button_type| {
+            trigger_axes
+                .get(GamepadButton::new(gamepad, button_type))
+                .unwrap_or(0.0)
+        };
+
+        let zoom =
+            trigger(GamepadButtonType::RightTrigger2) - trigger(GamepadButtonType::LeftTrigger2);
+
+        if zoom != 0.0 {
+            let scroll = 1.0 - zoom * ZOOM_SPEED * time.delta_seconds();
+            camera_transform.scale =
+                settings.clamp_zoom(camera_transform.scale * vec3(scroll, scroll, 1.0));
+        }
+    }
+}
+
+pub fn update_virtual_cursor_image(
+    virtual_cursor: Res<VirtualCursor>,
+    mut cursor_images: Query<(&mut Style, &mut Visibility), With<VirtualCursorImage>>,
+) {
+    for (mut style, mut visibility) in cursor_images.iter_mut() {
+        if visibility.is_visible != virtual_cursor.active {
+            visibility.is_visible = virtual_cursor.active;
+        }
+
+        if !virtual_cursor.active {
+            continue;
+        }
+
+        // Window positions start at the bottom left corner, like the UI offsets used here
+        let offset = virtual_cursor.position_window - Vec2::splat(CURSOR_SIZE / 2.0);
+
+        style.position = UiRect {
+            left: Val::Px(offset.x),
+            bottom: Val::Px(offset.y),
+            ..default()
+        };
+    }
+}
+
+fn stick(
+    axes: &Axis<GamepadAxis>,
+    gamepad: Gamepad,
+    x_axis: GamepadAxisType,
+    y_axis: GamepadAxisType,
+) -> Vec2 {
+    let value = |axis_type| {
+        axes.get(GamepadAxis::new(gamepad, axis_type))
+            .unwrap_or(0.0)
+    };
+
+    let tilt = Vec2::new(value(x_axis), value(y_axis));
+
+    if tilt.length() < STICK_DEADZONE {
+        Vec2::ZERO
+    } else {
+        tilt
+    }
+}
+
+/// Cursor driven by a gamepad, read by the world mouse in place of the real one while active.
+#[derive(Resource, Default)]
+pub struct VirtualCursor {
+    pub active: bool,
+    /// Same coordinates as `Window::cursor_position`.
+    pub position_window: Vec2,
+    pub buttons: Input<MouseButton>,
+}
+
+#[derive(Component)]
+pub struct VirtualCursorImage;
@@ -0,0 +1,192 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+
+use crate::{assets::Fonts, palette};
+
+use super::{
+    input::WorldMouse,
+    input_map::{Action, InputMap},
+    tile_tracked_entities::TilePosition,
+    TILE_SIZE,
+};
+
+pub fn setup_grid(mut commands: Commands, fonts: Res<Fonts>) {
+    commands.insert_resource(GridOverlay::default());
+
+    commands
+        .spawn(SpatialBundle::default())
+        .insert(Name::new("Grid"))
+        .insert(Grid);
+
+    commands
+        .spawn(TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: fonts.varela.clone(),
+                    color: palette::LIGHT_BROWN,
+                    font_size: 24.0,
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(16.0),
+                    top: Val::Px(16.0),
+                    ..default()
+                },
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(Name::new("Tile Readout"))
+        .insert(TileReadout);
+}
+
+pub fn toggle_grid(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut grid: ResMut<GridOverlay>,
+) {
+    if input_map.just_pressed(Action::ToggleGrid, &keys) {
+        grid.visible = !grid.visible;
+    }
+}
+
+type GridLines<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static mut Visibility,
+    ),
+    (With<GridLine>, Without<Camera2d>),
+>;
+
+/// Lays out grid lines over the part of the world seen by the camera.
+/// Lines are reused between frames and spawned only when the view needs more of them.
+pub fn update_grid(
+    mut commands: Commands,
+    overlay: Res<GridOverlay>,
+    windows: Res<Windows>,
+    camera: Query<&Transform, With<Camera2d>>,
+    grids: Query<Entity, With<Grid>>,
+    mut lines: GridLines,
+) {
+    if !overlay.visible {
+        // The lines only need hiding on the frame the overlay is turned off
+        if overlay.is_changed() {
+            for (_, _, mut visibility) in lines.iter_mut() {
+                if visibility.is_visible {
+                    visibility.is_visible = false;
+                }
+            }
+        }
+
+        return;
+    }
+
+    let (window, camera_transform) = match (windows.get_primary(), camera.get_single()) {
+        (Some(window), Ok(camera_transform)) => (window, camera_transform),
+        _ => return,
+    };
+
+    let scale = camera_transform.scale.x;
+    let center = camera_transform.translation.truncate();
+    let half_view = Vec2::new(window.width(), window.height()) * scale / 2.0;
+    let min = TilePosition::from_world(center - half_view);
+    let max = TilePosition::from_world(center + half_view).offset(1, 1);
+    let thickness = 2.0 * scale;
+
+    let vertical = (min.x..=max.x).map(|x| {
+        (
+            Vec2::new(x as f32 * TILE_SIZE, center.y),
+            Vec2::new(thickness, half_view.y * 2.0),
+        )
+    });
+    let horizontal = (min.y..=max.y).map(|y| {
+        (
+            Vec2::new(center.x, y as f32 * TILE_SIZE),
+            Vec2::new(half_view.x * 2.0, thickness),
+        )
+    });
+    let mut placements = vertical.chain(horizontal);
+
+    for (mut transform, mut sprite, mut visibility) in lines.iter_mut() {
+        match placements.next() {
+            Some((position, size)) => {
+                let translation = position.extend(-0.5);
+
+                if transform.translation != translation {
+                    transform.translation = translation;
+                }
+
+                if sprite.custom_size != Some(size) {
+                    sprite.custom_size = Some(size);
+                }
+
+                if !visibility.is_visible {
+                    visibility.is_visible = true;
+                }
+            }
+
+            None => {
+                if visibility.is_visible {
+                    visibility.is_visible = false;
+                }
+            }
+        }
+    }
+
+    let grid = if let Ok(grid) = grids.get_single() {
+        grid
+    } else {
+        return;
+    };
+
+    for (position, size) in placements {
+        let line = commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: palette::LIGHT_BLUE,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(position.extend(-0.5)),
+                ..default()
+            })
+            .insert(GridLine)
+            .id();
+
+        commands.entity(grid).add_child(line);
+    }
+}
+
+pub fn update_tile_readout(
+    world_mouse: Res<WorldMouse>,
+    mut readouts: Query<&mut Text, With<TileReadout>>,
+) {
+    let tile_position = TilePosition::from_world(world_mouse.position_world);
+    let label = format!("Tile {}, {}", tile_position.x, tile_position.y);
+
+    for mut text in readouts.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct GridOverlay {
+    pub visible: bool,
+}
+
+#[derive(Component)]
+pub struct Grid;
+
+#[derive(Component)]
+pub struct GridLine;
+
+#[derive(Component)]
+pub struct TileReadout;
@@ -0,0 +1,209 @@
+use bevy::prelude::*;
+
+use super::{
+    components::{Balance, Currency},
+    input::WorldMouse,
+    input_map::{Action, InputMap},
+    machines::{Machine, MachineDeleteRequest, MachinePlaceRequest, PlacedMachine},
+    tile_tracked_entities::{TilePosition, TileTrackedEntities},
+};
+
+/// Closes the group of edits made during the current gesture once the left button is released.
+pub fn commit_edit_group(
+    world_mouse: Res<WorldMouse>,
+    input_map: Res<InputMap>,
+    mut history: ResMut<EditHistory>,
+) {
+    if input_map.drag_start(Action::Place, &world_mouse).is_some() {
+        return;
+    }
+
+    if history.current.is_empty() {
+        return;
+    }
+
+    let group = std::mem::take(&mut history.current);
+    history.undo.push(group);
+    history.redo.clear();
+}
+
+pub fn undo_redo(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut history: ResMut<EditHistory>,
+    balance: Res<Balance>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    machines: Query<&PlacedMachine>,
+    mut machine_place_requests: EventWriter<MachinePlaceRequest>,
+    mut machine_delete_requests: EventWriter<MachineDeleteRequest>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if !ctrl {
+        return;
+    }
+
+    let undo = input_map.just_pressed(Action::Undo, &keys) && !shift;
+    let redo = input_map.just_pressed(Action::Redo, &keys)
+        || (input_map.just_pressed(Action::Undo, &keys) && shift);
+
+    if !undo && !redo {
+        return;
+    }
+
+    let occupied = |position| {
+        tile_tracked_entities
+            .get_entities_in_tile(position)
+            .is_some_and(|entities| entities.iter().any(|entity| machines.contains(*entity)))
+    };
+
+    let stack = if undo { &history.undo } else { &history.redo };
+
+    // A group moves to the other stack only when all of its edits can apply,
+    // the machine requests then change the balance for each of them
+    match stack.last() {
+        Some(group) if can_apply(group, undo, balance.coins, occupied) => {}
+        _ => return,
+    }
+
+    let group = if undo {
+        history.undo.pop().unwrap()
+    } else {
+        history.redo.pop().unwrap()
+    };
+
+    for edit in group.iter() {
+        // Undoing a placement deletes the machine, redoing it places the machine again
+        let deletes = matches!(edit, Edit::Place { .. }) == undo;
+
+        let (machine, position, price, enabled) = match *edit {
+            Edit::Place {
+                machine,
+                position,
+                cost,
+                enabled,
+            } => (machine, position, cost, enabled),
+
+            Edit::Delete {
+                machine,
+                position,
+                refund,
+                enabled,
+            } => (machine, position, refund, enabled),
+        };
+
+        if deletes {
+            machine_delete_requests.send(MachineDeleteRequest {
+                position,
+                history_refund: Some(price),
+                confirmed: true,
+            });
+        } else {
+            machine_place_requests.send(MachinePlaceRequest {
+                machine,
+                position,
+                history_cost: Some(price),
+                enabled,
+            });
+        }
+    }
+
+    if undo {
+        history.redo.push(group);
+    } else {
+        history.undo.push(group);
+    }
+}
+
+/// Whether the group can be undone, or redone, as a whole: every machine to delete is there,
+/// every tile to place on is free or emptied by the group, and the player can pay the difference.
+/// Deletions are applied before placements, so their refunds count.
+fn can_apply(
+    group: &[Edit],
+    undo: bool,
+    coins: Currency,
+    occupied: impl Fn(TilePosition) -> bool,
+) -> bool {
+    let deletes = |edit: &Edit| matches!(edit, Edit::Place { .. }) == undo;
+
+    let (cost, refund) = if undo {
+        (
+            group.iter().map(Edit::refund).sum::<Currency>(),
+            group.iter().map(Edit::cost).sum::<Currency>(),
+        )
+    } else {
+        (
+            group.iter().map(Edit::cost).sum::<Currency>(),
+            group.iter().map(Edit::refund).sum::<Currency>(),
+        )
+    };
+
+    if cost > coins + refund {
+        return false;
+    }
+
+    group.iter().all(|edit| {
+        if deletes(edit) {
+            occupied(edit.position())
+        } else {
+            !occupied(edit.position())
+                || group
+                    .iter()
+                    .any(|other| deletes(other) && other.position() == edit.position())
+        }
+    })
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Edit {
+    Place {
+        machine: Machine,
+        position: TilePosition,
+        cost: Currency,
+        enabled: bool,
+    },
+
+    Delete {
+        machine: Machine,
+        position: TilePosition,
+        refund: Currency,
+        enabled: bool,
+    },
+}
+
+impl Edit {
+    pub fn position(&self) -> TilePosition {
+        match self {
+            Edit::Place { position, .. } | Edit::Delete { position, .. } => *position,
+        }
+    }
+
+    pub fn cost(&self) -> Currency {
+        match self {
+            Edit::Place { cost, .. } => *cost,
+            Edit::Delete { .. } => 0,
+        }
+    }
+
+    pub fn refund(&self) -> Currency {
+        match self {
+            Edit::Place { .. } => 0,
+            Edit::Delete { refund, .. } => *refund,
+        }
+    }
+}
+
+/// Undo and redo stacks of placement and deletion edits, grouped per gesture.
+#[derive(Resource, Default)]
+pub struct EditHistory {
+    undo: Vec<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    current: Vec<Edit>,
+}
+
+impl EditHistory {
+    pub fn record(&mut self, edit: Edit) {
+        self.current.push(edit);
+    }
+}
@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+
+use super::{
+    components::Simulation,
+    hud::{ToolGhost, Toolbar, ToolbarButton, ToolbarButtonDelete, ToolbarButtonSelectedEvent},
+    input_map::{Action, InputMap},
+    machines::Machine,
+};
+
+/// Number keys in the order of the toolbar entries they select.
+const TOOLBAR_KEYS: [KeyCode; 10] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+];
+
+/// Number keys pick toolbar entries from left to right, the delete tool has its own action.
+/// Picking the selected entry again drops it, like clicking it does.
+pub fn select_tool_on_key(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    toolbars: Query<&Children, With<Toolbar>>,
+    buttons: Query<&ToolbarButton>,
+    delete_buttons: Query<Entity, (With<ToolbarButton>, With<ToolbarButtonDelete>)>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if ctrl {
+        return;
+    }
+
+    let mut pressed_button = None;
+
+    if let Ok(toolbar_entries) = toolbars.get_single() {
+        for (key, &entity) in TOOLBAR_KEYS.iter().zip(toolbar_entries.iter()) {
+            if keys.just_pressed(*key) {
+                pressed_button = Some(entity);
+            }
+        }
+    }
+
+    if input_map.just_pressed(Action::DeleteTool, &keys) {
+        pressed_button = delete_buttons.get_single().ok();
+    }
+
+    let (entity, button) = match pressed_button
+        .and_then(|entity| buttons.get(entity).ok().map(|button| (entity, button)))
+    {
+        Some(pressed) => pressed,
+        None => return,
+    };
+
+    if !button.enabled {
+        return;
+    }
+
+    if button.selected {
+        button_selected_events.send(ToolbarButtonSelectedEvent(None));
+    } else {
+        button_selected_events.send(ToolbarButtonSelectedEvent(Some(entity)));
+    }
+}
+
+pub fn drop_tool_on_cancel(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    tool_ghosts: Query<(), With<ToolGhost>>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    if input_map.just_pressed(Action::Cancel, &keys) && !tool_ghosts.is_empty() {
+        button_selected_events.send(ToolbarButtonSelectedEvent(None));
+    }
+}
+
+/// Conveyors have a toolbar entry per direction, so rotating the ghost
+/// switches to the entry of the turned conveyor.
+pub fn rotate_tool_on_key(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    tool_ghosts: Query<&Machine, With<ToolGhost>>,
+    machine_buttons: Query<(Entity, &Machine, &ToolbarButton), Without<ToolGhost>>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if !input_map.just_pressed(Action::Rotate, &keys) || ctrl {
+        return;
+    }
+
+    let rotated = match tool_ghosts.get_single() {
+        Ok(machine) if machine.rotated() != *machine => machine.rotated(),
+        _ => return,
+    };
+
+    for (entity, machine, button) in machine_buttons.iter() {
+        if *machine == rotated && button.enabled {
+            button_selected_events.send(ToolbarButtonSelectedEvent(Some(entity)));
+        }
+    }
+}
+
+pub fn toggle_pause(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut simulation: ResMut<Simulation>,
+) {
+    if input_map.just_pressed(Action::Pause, &keys) {
+        simulation.paused = !simulation.paused;
+    }
+}
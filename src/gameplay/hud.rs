@@ -1,6 +1,7 @@
 use std::time::Duration;
 
-use bevy::{prelude::*, ui::FocusPolicy};
+use bevy::{ecs::system::EntityCommands, math::vec2, prelude::*, ui::FocusPolicy};
+use bevy_kira_audio::prelude::{Audio, AudioControl};
 use bevy_ninepatch::{NinePatchBundle, NinePatchData};
 use bevy_tweening::{
     lens::{TransformPositionLens, UiPositionLens},
@@ -8,16 +9,24 @@ use bevy_tweening::{
 };
 
 use crate::{
-    assets::{Fonts, Images, NinePatches},
+    assets::{Fonts, Images, NinePatches, Sounds},
+    common::components::DelayedDespawn,
     palette,
 };
 
 use super::{
-    components::Balance,
-    input::{MouseButtonState, WorldMouse, WorldMouseEvent},
-    machines::{Machine, MachineDeleteRequest, MachinePlaceRequest},
-    tile_tracked_entities::TilePosition,
-    TILE_SIZE,
+    blueprints::{BlueprintClipboard, SelectionRect},
+    components::{Balance, CoinMerging, Currency, Income, Simulation},
+    input::{WorldMouse, WorldMouseEvent},
+    input_map::{Action, InputMap},
+    machines::{
+        check_placement, DeleteConfirmation, Machine, MachineDeleteRequest, MachinePlaceRejected,
+        MachinePlaceRequest, PlacedMachine,
+    },
+    selection::is_box_selecting,
+    settings::Settings,
+    tile_tracked_entities::{TilePosition, TileTrackedEntities},
+    HALF_TILE_SIZE, TILE_SIZE,
 };
 
 pub fn setup_hud(
@@ -107,8 +116,80 @@ pub fn setup_hud(
                                 })
                                 .insert(Name::new("Value"))
                                 .insert(MoneyDisplay);
+
+                            money_display
+                                .spawn(ButtonBundle {
+                                    style: Style {
+                                        align_self: AlignSelf::Center,
+                                        margin: UiRect {
+                                            left: Val::Px(16.0),
+                                            ..default()
+                                        },
+                                        ..default()
+                                    },
+                                    background_color: Color::NONE.into(),
+                                    ..default()
+                                })
+                                .with_children(|income_button| {
+                                    income_button
+                                        .spawn(TextBundle {
+                                            text: Text::from_section(
+                                                "",
+                                                TextStyle {
+                                                    font: fonts.varela.clone(),
+                                                    color: palette::LIGHT_BROWN,
+                                                    font_size: 24.0,
+                                                },
+                                            ),
+                                            focus_policy: FocusPolicy::Pass,
+                                            ..default()
+                                        })
+                                        .insert(IncomeDisplay);
+                                })
+                                .insert(Name::new("Income"))
+                                .insert(IncomeButton);
                         })
                         .insert(Name::new("Money Display"));
+
+                    top_panel
+                        .spawn(TextBundle {
+                            text: Text::from_section(
+                                "Paused",
+                                TextStyle {
+                                    font: fonts.varela.clone(),
+                                    color: palette::ORANGE,
+                                    font_size: 48.0,
+                                },
+                            ),
+                            style: Style {
+                                margin: UiRect::all(Val::Px(8.0)),
+                                ..default()
+                            },
+                            visibility: Visibility { is_visible: false },
+                            ..default()
+                        })
+                        .insert(Name::new("Pause Label"))
+                        .insert(PauseLabel);
+
+                    top_panel
+                        .spawn(TextBundle {
+                            text: Text::from_section(
+                                "Merging coins",
+                                TextStyle {
+                                    font: fonts.varela.clone(),
+                                    color: palette::BLUE,
+                                    font_size: 32.0,
+                                },
+                            ),
+                            style: Style {
+                                margin: UiRect::all(Val::Px(8.0)),
+                                ..default()
+                            },
+                            visibility: Visibility { is_visible: false },
+                            ..default()
+                        })
+                        .insert(Name::new("Merging Label"))
+                        .insert(MergingLabel);
                 })
                 .insert(Name::new("Top Panel"))
                 .insert(Animator::new(Tween::new(
@@ -214,77 +295,33 @@ pub fn setup_hud(
                             .insert(*machine);
                     }
 
-                    bottom_panel
-                        .spawn(ButtonBundle {
-                            style: Style {
-                                flex_direction: FlexDirection::Column,
-                                padding: UiRect::all(Val::Px(8.0)),
-                                align_items: AlignItems::Center,
-                                size: Size {
-                                    width: Val::Px(90.0),
-                                    height: Val::Undefined,
-                                },
-                                ..default()
-                            },
-                            background_color: Color::NONE.into(),
+                    spawn_tool_button(bottom_panel, &fonts, "Delete", images.delete.clone())
+                        .insert(ToolbarButton {
+                            enabled: true,
                             ..default()
                         })
-                        .with_children(|container| {
-                            container.spawn(TextBundle {
-                                text: Text::from_section(
-                                    "Delete",
-                                    TextStyle {
-                                        font: fonts.varela.clone(),
-                                        color: palette::LIGHT_BROWN,
-                                        font_size: 20.0,
-                                    },
-                                )
-                                .with_alignment(TextAlignment::BOTTOM_CENTER),
-                                style: Style {
-                                    margin: UiRect {
-                                        bottom: Val::Px(4.0),
-                                        ..default()
-                                    },
-                                    max_size: Size {
-                                        width: Val::Px(90.0),
-                                        height: default(),
-                                    },
-                                    ..default()
-                                },
-                                focus_policy: FocusPolicy::Pass,
-                                ..default()
-                            });
-
-                            container.spawn(ImageBundle {
-                                image: images.delete.clone().into(),
-                                style: Style {
-                                    size: Size::new(Val::Px(64.0), Val::Px(64.0)),
-                                    ..default()
-                                },
-                                focus_policy: FocusPolicy::Pass,
-                                ..default()
-                            });
+                        .insert(ToolbarButtonDelete);
 
-                            container.spawn(TextBundle {
-                                text: Text::from_section(
-                                    " ".to_string(),
-                                    TextStyle {
-                                        font: fonts.varela.clone(),
-                                        color: palette::DARK_BLUE,
-                                        font_size: 28.0,
-                                    },
-                                ),
-                                focus_policy: FocusPolicy::Pass,
-                                ..default()
-                            });
+                    spawn_tool_button(bottom_panel, &fonts, "Toggle", images.toggle.clone())
+                        .insert(ToolbarButton {
+                            enabled: true,
+                            ..default()
                         })
+                        .insert(ToolbarButtonToggle);
+
+                    spawn_tool_button(bottom_panel, &fonts, "Copy", images.copy.clone())
                         .insert(ToolbarButton {
                             enabled: true,
                             ..default()
                         })
-                        .insert(ToolbarButtonDelete);
+                        .insert(ToolbarButtonCopy);
+
+                    spawn_tool_button(bottom_panel, &fonts, "Paste", images.locked.clone())
+                        .insert(ToolbarButton::default())
+                        .insert(ToolbarButtonPaste);
                 })
                 .insert(Name::new("Bottom Panel Content"))
+                .insert(Toolbar)
                 .id();
 
             window
@@ -318,6 +355,109 @@ pub fn setup_hud(
         });
 }
 
+fn spawn_tool_button<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    fonts: &Fonts,
+    name: &str,
+    image: Handle<Image>,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut button = parent.spawn(ButtonBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(8.0)),
+            align_items: AlignItems::Center,
+            size: Size {
+                width: Val::Px(90.0),
+                height: Val::Undefined,
+            },
+            ..default()
+        },
+        background_color: Color::NONE.into(),
+        ..default()
+    });
+
+    button.with_children(|container| {
+        container.spawn(TextBundle {
+            text: Text::from_section(
+                name,
+                TextStyle {
+                    font: fonts.varela.clone(),
+                    color: palette::LIGHT_BROWN,
+                    font_size: 20.0,
+                },
+            )
+            .with_alignment(TextAlignment::BOTTOM_CENTER),
+            style: Style {
+                margin: UiRect {
+                    bottom: Val::Px(4.0),
+                    ..default()
+                },
+                max_size: Size {
+                    width: Val::Px(90.0),
+                    height: default(),
+                },
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        });
+
+        container
+            .spawn(ImageBundle {
+                image: image.into(),
+                style: Style {
+                    size: Size::new(Val::Px(64.0), Val::Px(64.0)),
+                    ..default()
+                },
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            })
+            .insert(ToolIcon);
+
+        container.spawn(TextBundle {
+            text: Text::from_section(
+                " ".to_string(),
+                TextStyle {
+                    font: fonts.varela.clone(),
+                    color: palette::DARK_BLUE,
+                    font_size: 28.0,
+                },
+            ),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        });
+    });
+
+    button
+}
+
+pub fn update_paste_button(
+    clipboard: Res<BlueprintClipboard>,
+    images: Res<Images>,
+    mut paste_buttons: Query<(&mut ToolbarButton, &Children), With<ToolbarButtonPaste>>,
+    mut tool_icons: Query<&mut UiImage, With<ToolIcon>>,
+) {
+    if !clipboard.is_changed() {
+        return;
+    }
+
+    let has_blueprint = clipboard.blueprint.is_some();
+
+    for (mut button, children) in paste_buttons.iter_mut() {
+        button.enabled = has_blueprint;
+
+        for &child in children.iter() {
+            if let Ok(mut image) = tool_icons.get_mut(child) {
+                image.0 = if has_blueprint {
+                    images.paste.clone()
+                } else {
+                    images.locked.clone()
+                };
+            }
+        }
+    }
+}
+
 pub fn update_balance_display(
     wallet: Res<Balance>,
     ui_images: Res<Images>,
@@ -353,6 +493,43 @@ pub fn update_balance_display(
     }
 }
 
+/// Coins per second next to the balance, clicking it opens the balance graph.
+pub fn update_income_display(
+    income: Res<Income>,
+    time: Res<Time>,
+    mut income_displays: Query<&mut Text, With<IncomeDisplay>>,
+) {
+    let value = format!("+{:.1}/s", income.per_second(time.elapsed_seconds_f64()));
+
+    for mut text in income_displays.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+pub fn update_pause_label(
+    simulation: Res<Simulation>,
+    mut pause_labels: Query<&mut Visibility, With<PauseLabel>>,
+) {
+    for mut visibility in pause_labels.iter_mut() {
+        if visibility.is_visible != simulation.paused {
+            visibility.is_visible = simulation.paused;
+        }
+    }
+}
+
+pub fn update_merging_label(
+    coin_merging: Res<CoinMerging>,
+    mut merging_labels: Query<&mut Visibility, With<MergingLabel>>,
+) {
+    for mut visibility in merging_labels.iter_mut() {
+        if visibility.is_visible != coin_merging.enabled {
+            visibility.is_visible = coin_merging.enabled;
+        }
+    }
+}
+
 pub fn select_toolbar_button(
     buttons: Query<(Entity, &Interaction, &ToolbarButton), Changed<Interaction>>,
     mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
@@ -376,6 +553,76 @@ pub fn select_toolbar_button(
     }
 }
 
+/// Selects the toolbar button of the machine under the cursor when it is clicked with the pick button.
+pub fn pick_machine_on_click(
+    mut world_mouse_events: EventReader<WorldMouseEvent>,
+    input_map: Res<InputMap>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    machines: Query<&PlacedMachine>,
+    machine_buttons: Query<(Entity, &Machine, &ToolbarButton)>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    for event in world_mouse_events.iter() {
+        if let WorldMouseEvent::Click { button, position } = event {
+            if !input_map.is_button(Action::Pick, *button) {
+                continue;
+            }
+
+            pick_machine(
+                TilePosition::from_world(*position),
+                &tile_tracked_entities,
+                &machines,
+                &machine_buttons,
+                &mut button_selected_events,
+            );
+        }
+    }
+}
+
+pub fn pick_machine_on_key(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    world_mouse: Res<WorldMouse>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    machines: Query<&PlacedMachine>,
+    machine_buttons: Query<(Entity, &Machine, &ToolbarButton)>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    if input_map.just_pressed(Action::Pick, &keys) {
+        pick_machine(
+            TilePosition::from_world(world_mouse.position_world),
+            &tile_tracked_entities,
+            &machines,
+            &machine_buttons,
+            &mut button_selected_events,
+        );
+    }
+}
+
+fn pick_machine(
+    position: TilePosition,
+    tile_tracked_entities: &TileTrackedEntities,
+    machines: &Query<&PlacedMachine>,
+    machine_buttons: &Query<(Entity, &Machine, &ToolbarButton)>,
+    button_selected_events: &mut EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    let picked_machine = tile_tracked_entities
+        .get_entities_in_tile(position)
+        .and_then(|entities| {
+            entities
+                .iter()
+                .find_map(|entity| machines.get(*entity).ok())
+        });
+
+    if let Some(placed_machine) = picked_machine {
+        for (entity, machine, button) in machine_buttons.iter() {
+            if *machine == placed_machine.machine && button.enabled && !button.selected {
+                button_selected_events.send(ToolbarButtonSelectedEvent(Some(entity)));
+            }
+        }
+    }
+}
+
 pub fn update_selected_machine_button(
     mut commands: Commands,
     mut buttons: Query<(Entity, &mut ToolbarButton, &Style)>,
@@ -424,7 +671,12 @@ pub fn show_hide_building_ghost(
     mut button_selected_events: EventReader<ToolbarButtonSelectedEvent>,
     machine_buttons: Query<&Machine, (With<ToolbarButton>, Without<ToolbarButtonDelete>)>,
     delete_buttons: Query<&ToolbarButtonDelete, With<ToolbarButton>>,
+    toggle_buttons: Query<&ToolbarButtonToggle, With<ToolbarButton>>,
+    copy_buttons: Query<&ToolbarButtonCopy, With<ToolbarButton>>,
+    paste_buttons: Query<&ToolbarButtonPaste, With<ToolbarButton>>,
+    clipboard: Res<BlueprintClipboard>,
     images: Res<Images>,
+    fonts: Res<Fonts>,
     world_mouse: Res<WorldMouse>,
     building_ghosts: Query<Entity, With<ToolGhost>>,
 ) {
@@ -439,7 +691,26 @@ pub fn show_hide_building_ghost(
             let ghost_entity = if let Ok(machine) = machine_buttons.get(selected) {
                 let entity = machine.spawn_graphics(&mut commands, &images, false);
 
-                commands.entity(entity).insert(*machine);
+                commands
+                    .entity(entity)
+                    .with_children(|ghost| {
+                        ghost
+                            .spawn(Text2dBundle {
+                                text: Text::from_section(
+                                    "",
+                                    TextStyle {
+                                        font: fonts.varela.clone(),
+                                        color: palette::ORANGE,
+                                        font_size: 64.0,
+                                    },
+                                )
+                                .with_alignment(TextAlignment::BOTTOM_CENTER),
+                                transform: Transform::from_xyz(0.0, TILE_SIZE / 2.0, 0.1),
+                                ..default()
+                            })
+                            .insert(PlacementLabel);
+                    })
+                    .insert(*machine);
 
                 Some(entity)
             } else if let Ok(_) = delete_buttons.get(selected) {
@@ -448,9 +719,63 @@ pub fn show_hide_building_ghost(
                         texture: images.delete.clone(),
                         ..default()
                     })
+                    .with_children(|ghost| {
+                        ghost
+                            .spawn(Text2dBundle {
+                                text: Text::from_section(
+                                    "",
+                                    TextStyle {
+                                        font: fonts.varela.clone(),
+                                        color: palette::DARK_BLUE,
+                                        font_size: 64.0,
+                                    },
+                                )
+                                .with_alignment(TextAlignment::BOTTOM_CENTER),
+                                transform: Transform::from_xyz(0.0, TILE_SIZE / 2.0, 0.1),
+                                ..default()
+                            })
+                            .insert(RefundLabel);
+                    })
                     .insert(ToolbarButtonDelete)
                     .id();
 
+                Some(entity)
+            } else if let Ok(_) = toggle_buttons.get(selected) {
+                let entity = commands
+                    .spawn(SpriteBundle {
+                        texture: images.toggle.clone(),
+                        ..default()
+                    })
+                    .insert(ToolbarButtonToggle)
+                    .id();
+
+                Some(entity)
+            } else if let Ok(_) = copy_buttons.get(selected) {
+                let entity = commands
+                    .spawn(SpatialBundle::default())
+                    .with_children(|ghost| {
+                        ghost
+                            .spawn(SpriteBundle {
+                                sprite: Sprite {
+                                    color: palette::SELECTION,
+                                    custom_size: Some(Vec2::splat(TILE_SIZE)),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .insert(SelectionRect);
+                    })
+                    .insert(ToolbarButtonCopy)
+                    .id();
+
+                Some(entity)
+            } else if let (Ok(_), Some(blueprint)) =
+                (paste_buttons.get(selected), &clipboard.blueprint)
+            {
+                let entity = blueprint.spawn_graphics(&mut commands, &images, &fonts);
+
+                commands.entity(entity).insert(ToolbarButtonPaste);
+
                 Some(entity)
             } else {
                 None
@@ -471,18 +796,19 @@ pub fn show_hide_building_ghost(
     }
 }
 
-pub fn hide_building_ghost_on_right_click(
+pub fn hide_building_ghost_on_cancel(
     building_ghosts: Query<Entity, With<ToolGhost>>,
+    input_map: Res<InputMap>,
     mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
     mut world_mouse_events: EventReader<WorldMouseEvent>,
 ) {
     if !building_ghosts.is_empty() {
         for event in world_mouse_events.iter() {
-            if let WorldMouseEvent::Click {
-                button: MouseButton::Right,
-                ..
-            } = event
-            {
+            if let WorldMouseEvent::Click { button, .. } = event {
+                if !input_map.is_button(Action::Cancel, *button) {
+                    continue;
+                }
+
                 button_selected_events.send(ToolbarButtonSelectedEvent(None));
                 break;
             }
@@ -495,9 +821,13 @@ pub fn hide_building_ghost_on_right_click(
 pub fn drag_building_ghost(
     mut commands: Commands,
     world_mouse: Res<WorldMouse>,
+    input_map: Res<InputMap>,
     mut building_ghosts: Query<(Entity, &mut ToolGhost, &Transform)>,
 ) {
-    if let MouseButtonState::Dragging { .. } = world_mouse.button_state_middle {
+    if input_map
+        .drag_start(Action::HoldGhost, &world_mouse)
+        .is_some()
+    {
         return;
     }
 
@@ -523,51 +853,372 @@ pub fn drag_building_ghost(
     }
 }
 
+pub fn update_refund_label(
+    world_mouse: Res<WorldMouse>,
+    settings: Res<Settings>,
+    confirmation: Res<DeleteConfirmation>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    machines: Query<&PlacedMachine>,
+    mut refund_labels: Query<&mut Text, With<RefundLabel>>,
+) {
+    let tile_position = TilePosition::from_world(world_mouse.position_world);
+
+    let hovered_machine = tile_tracked_entities
+        .get_entities_in_tile(tile_position)
+        .and_then(|entities| {
+            entities
+                .iter()
+                .find_map(|entity| machines.get(*entity).ok())
+        });
+
+    let label = match hovered_machine {
+        Some(placed_machine) if confirmation.positions.contains(&tile_position) => format!(
+            "Click again to delete\n+{}",
+            settings.refund(placed_machine.machine)
+        ),
+        Some(placed_machine) => format!("+{}", settings.refund(placed_machine.machine)),
+        None => String::new(),
+    };
+
+    for mut text in refund_labels.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+/// Tints the machine ghost and explains why it can't be placed on the hovered tile.
+pub fn update_placement_label(
+    world_mouse: Res<WorldMouse>,
+    balance: Res<Balance>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    machines: Query<&PlacedMachine>,
+    mut tool_ghosts: Query<(&Machine, &mut Sprite), With<ToolGhost>>,
+    mut placement_labels: Query<&mut Text, With<PlacementLabel>>,
+) {
+    let tile_position = TilePosition::from_world(world_mouse.position_world);
+
+    for (machine, mut sprite) in tool_ghosts.iter_mut() {
+        let placement = check_placement(
+            machine.cost(),
+            tile_position,
+            balance.coins,
+            &tile_tracked_entities,
+            &machines,
+        );
+
+        let (color, label) = match placement {
+            Ok(()) => (Color::WHITE, ""),
+            Err(reason) => (palette::INVALID_GHOST, reason.message()),
+        };
+
+        if sprite.color != color {
+            sprite.color = color;
+        }
+
+        for mut text in placement_labels.iter_mut() {
+            if text.sections[0].value != label {
+                text.sections[0].value = label.to_string();
+            }
+        }
+    }
+}
+
+/// Floats the reason of a rejected placement above the tile.
+/// A drag rejected on many tiles only shows each reason once.
+pub fn show_placement_rejections(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    sounds: Res<Sounds>,
+    audio: Res<Audio>,
+    mut rejections: EventReader<MachinePlaceRejected>,
+) {
+    let mut shown_reasons = Vec::new();
+
+    for rejection in rejections.iter() {
+        if shown_reasons.contains(&rejection.reason) {
+            continue;
+        }
+
+        shown_reasons.push(rejection.reason);
+
+        let start = (rejection.position.to_world() + vec2(HALF_TILE_SIZE, TILE_SIZE)).extend(1.0);
+        let duration = Duration::from_secs_f32(0.8);
+
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(
+                    rejection.reason.message(),
+                    TextStyle {
+                        font: fonts.varela.clone(),
+                        color: palette::ORANGE,
+                        font_size: 64.0,
+                    },
+                )
+                .with_alignment(TextAlignment::BOTTOM_CENTER),
+                transform: Transform::from_translation(start),
+                ..default()
+            })
+            .insert(Animator::new(Tween::new(
+                EaseFunction::CubicOut,
+                duration,
+                TransformPositionLens {
+                    start,
+                    end: start + Vec3::Y * TILE_SIZE,
+                },
+            )))
+            .insert(DelayedDespawn::with_children(duration));
+    }
+
+    if !shown_reasons.is_empty() {
+        audio.play(sounds.reject.clone());
+    }
+}
+
+/// Shows the tools along the whole drag path before the button is released.
+pub fn update_drag_preview(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    world_mouse: Res<WorldMouse>,
+    balance: Res<Balance>,
+    images: Res<Images>,
+    fonts: Res<Fonts>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    machines: Query<&PlacedMachine>,
+    tool_ghosts: Query<(Option<&Machine>, Option<&ToolbarButtonDelete>), With<ToolGhost>>,
+    previews: Query<(Entity, &DragPreview)>,
+    mut preview_tiles: Query<(&mut Sprite, &DragPreviewTile)>,
+    mut cost_labels: Query<&mut Text, With<DragPreviewCost>>,
+) {
+    let plan = match (
+        tool_ghosts.get_single(),
+        input_map.drag_start(Action::Place, &world_mouse),
+    ) {
+        (Ok(tool), Some(start_world)) if !is_box_selecting(&keys) => {
+            let start_tile = TilePosition::from_world(start_world);
+            let end_tile = TilePosition::from_world(world_mouse.position_world);
+
+            match tool {
+                (Some(machine), _) => Some(drag_plan(*machine, start_tile, end_tile)),
+                (None, Some(_)) => Some(
+                    start_tile
+                        .line_to(end_tile)
+                        .into_iter()
+                        .map(|tile| (tile, None))
+                        .collect(),
+                ),
+                (None, None) => None,
+            }
+        }
+
+        _ => None,
+    };
+
+    let is_up_to_date = match (&plan, previews.get_single()) {
+        (Some(plan), Ok((_, preview))) => preview.plan == *plan,
+        (None, Err(_)) => true,
+        _ => false,
+    };
+
+    if !is_up_to_date {
+        for (entity, _) in previews.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        if let Some(plan) = plan {
+            spawn_drag_preview(&mut commands, &images, &fonts, plan);
+        }
+
+        return;
+    }
+
+    let plan = if let Some(plan) = plan {
+        plan
+    } else {
+        return;
+    };
+
+    let is_occupied = |position: TilePosition| {
+        tile_tracked_entities
+            .get_entities_in_tile(position)
+            .is_some_and(|entities| entities.iter().any(|entity| machines.contains(*entity)))
+    };
+
+    let cost: Currency = plan
+        .iter()
+        .filter(|(tile, _)| !is_occupied(*tile))
+        .filter_map(|(_, machine)| machine.map(|machine| machine.cost()))
+        .sum();
+    let affordable = cost <= balance.coins;
+
+    for (mut sprite, DragPreviewTile(position)) in preview_tiles.iter_mut() {
+        sprite.color = if affordable && !is_occupied(*position) {
+            palette::VALID_GHOST
+        } else {
+            palette::INVALID_GHOST
+        };
+    }
+
+    for mut text in cost_labels.iter_mut() {
+        let label = cost.to_string();
+
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+
+        text.sections[0].style.color = if affordable {
+            palette::DARK_BLUE
+        } else {
+            palette::ORANGE
+        };
+    }
+}
+
+/// Machines to place along a drag. Conveyors follow an L-shaped path
+/// and are turned to face the next tile of the path.
+pub fn drag_plan(
+    machine: Machine,
+    start_tile: TilePosition,
+    end_tile: TilePosition,
+) -> Vec<(TilePosition, Option<Machine>)> {
+    if !machine.is_conveyor() || start_tile == end_tile {
+        return start_tile
+            .line_to(end_tile)
+            .into_iter()
+            .map(|tile| (tile, Some(machine)))
+            .collect();
+    }
+
+    let path = start_tile.l_path_to(end_tile);
+
+    path.iter()
+        .enumerate()
+        .map(|(i, &tile)| {
+            let (from, to) = if i + 1 < path.len() {
+                (tile, path[i + 1])
+            } else {
+                (path[i - 1], tile)
+            };
+
+            let conveyor = Machine::conveyor_towards(to.x - from.x, to.y - from.y);
+
+            (tile, Some(conveyor))
+        })
+        .collect()
+}
+
+fn spawn_drag_preview(
+    commands: &mut Commands,
+    images: &Images,
+    fonts: &Fonts,
+    plan: Vec<(TilePosition, Option<Machine>)>,
+) {
+    let preview_tiles: Vec<_> = plan
+        .iter()
+        .map(|&(tile, machine)| {
+            let entity = match machine {
+                Some(machine) => machine.spawn_graphics(commands, images, false),
+                None => commands
+                    .spawn(SpriteBundle {
+                        texture: images.delete.clone(),
+                        ..default()
+                    })
+                    .id(),
+            };
+
+            commands
+                .entity(entity)
+                .insert(Transform::from_translation(
+                    (tile.to_world() + Vec2::splat(HALF_TILE_SIZE)).extend(0.1),
+                ))
+                .insert(DragPreviewTile(tile));
+
+            entity
+        })
+        .collect();
+
+    let cost_label_tile = match plan.last() {
+        Some(&(tile, Some(_))) => Some(tile),
+        _ => None,
+    };
+
+    let preview = commands
+        .spawn(SpatialBundle::default())
+        .push_children(&preview_tiles)
+        .insert(DragPreview { plan })
+        .insert(Name::new("Drag Preview"))
+        .id();
+
+    if let Some(last_tile) = cost_label_tile {
+        let cost_label = commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font: fonts.varela.clone(),
+                        color: palette::DARK_BLUE,
+                        font_size: 96.0,
+                    },
+                )
+                .with_alignment(TextAlignment::BOTTOM_CENTER),
+                transform: Transform::from_translation(
+                    (last_tile.to_world() + vec2(HALF_TILE_SIZE, TILE_SIZE)).extend(0.2),
+                ),
+                ..default()
+            })
+            .insert(DragPreviewCost)
+            .id();
+
+        commands.entity(preview).add_child(cost_label);
+    }
+}
+
 pub fn ghost_place_machine(
     mut machine_place_requests: EventWriter<MachinePlaceRequest>,
     mut world_mouse_events: EventReader<WorldMouseEvent>,
     building_ghosts: Query<&Machine, With<ToolGhost>>,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
 ) {
+    if is_box_selecting(&keys) {
+        world_mouse_events.clear();
+        return;
+    }
+
     if let Ok(machine) = building_ghosts.get_single() {
         for event in world_mouse_events.iter() {
             match event {
-                WorldMouseEvent::Click {
-                    button: MouseButton::Left,
-                    position,
-                } => {
+                WorldMouseEvent::Click { button, position }
+                    if input_map.is_button(Action::Place, *button) =>
+                {
                     let tile_position = TilePosition::from_world(*position);
 
                     machine_place_requests.send(MachinePlaceRequest {
                         machine: *machine,
                         position: tile_position,
+                        history_cost: None,
+                        enabled: true,
                     });
                 }
 
-                WorldMouseEvent::Drag {
-                    button: MouseButton::Left,
+                WorldMouseEvent::DragEnd {
+                    button,
                     start_world,
                     end_world,
-                } => {
+                } if input_map.is_button(Action::Place, *button) => {
                     let start_tile = TilePosition::from_world(*start_world);
                     let end_tile = TilePosition::from_world(*end_world);
 
-                    let num_steps = i32::max(
-                        (end_tile.x - start_tile.x).abs(),
-                        (end_tile.y - start_tile.y).abs(),
-                    )
-                    .max(1);
-
-                    let start_tile_vec = start_tile.to_vec();
-                    let step_vec = (end_tile.to_vec() - start_tile_vec) / (num_steps as f32);
-
-                    for i in 0..num_steps {
-                        let pos_vec = start_tile_vec + step_vec * (i as f32);
-                        let tile_position = TilePosition::from_vec(pos_vec);
-
-                        machine_place_requests.send(MachinePlaceRequest {
-                            machine: *machine,
-                            position: tile_position,
-                        });
+                    for (tile_position, machine) in drag_plan(*machine, start_tile, end_tile) {
+                        if let Some(machine) = machine {
+                            machine_place_requests.send(MachinePlaceRequest {
+                                machine,
+                                position: tile_position,
+                                history_cost: None,
+                                enabled: true,
+                            });
+                        }
                     }
                 }
 
@@ -581,44 +1232,42 @@ pub fn ghost_delete_machine(
     mut machine_delete_requests: EventWriter<MachineDeleteRequest>,
     mut world_mouse_events: EventReader<WorldMouseEvent>,
     building_ghosts: Query<&ToolbarButtonDelete, With<ToolGhost>>,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
 ) {
+    if is_box_selecting(&keys) {
+        world_mouse_events.clear();
+        return;
+    }
+
     if let Ok(_) = building_ghosts.get_single() {
         for event in world_mouse_events.iter() {
             match event {
-                WorldMouseEvent::Click {
-                    button: MouseButton::Left,
-                    position,
-                } => {
+                WorldMouseEvent::Click { button, position }
+                    if input_map.is_button(Action::Place, *button) =>
+                {
                     let tile_position = TilePosition::from_world(*position);
 
                     machine_delete_requests.send(MachineDeleteRequest {
                         position: tile_position,
+                        history_refund: None,
+                        confirmed: false,
                     });
                 }
 
-                WorldMouseEvent::Drag {
-                    button: MouseButton::Left,
+                WorldMouseEvent::DragEnd {
+                    button,
                     start_world,
                     end_world,
-                } => {
+                } if input_map.is_button(Action::Place, *button) => {
                     let start_tile = TilePosition::from_world(*start_world);
                     let end_tile = TilePosition::from_world(*end_world);
 
-                    let num_steps = i32::max(
-                        (end_tile.x - start_tile.x).abs(),
-                        (end_tile.y - start_tile.y).abs(),
-                    )
-                    .max(1);
-
-                    let start_tile_vec = start_tile.to_vec();
-                    let step_vec = (end_tile.to_vec() - start_tile_vec) / (num_steps as f32);
-
-                    for i in 0..num_steps {
-                        let pos_vec = start_tile_vec + step_vec * (i as f32);
-                        let tile_position = TilePosition::from_vec(pos_vec);
-
+                    for tile_position in start_tile.line_to(end_tile) {
                         machine_delete_requests.send(MachineDeleteRequest {
                             position: tile_position,
+                            history_refund: None,
+                            confirmed: false,
                         });
                     }
                 }
@@ -629,11 +1278,72 @@ pub fn ghost_delete_machine(
     }
 }
 
-pub struct ToolbarButtonSelectedEvent(Option<Entity>);
+/// Turns the clicked machines, or the machines along a drag, on or off.
+pub fn ghost_toggle_machine(
+    mut world_mouse_events: EventReader<WorldMouseEvent>,
+    building_ghosts: Query<&ToolbarButtonToggle, With<ToolGhost>>,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    mut machines: Query<&mut PlacedMachine>,
+) {
+    if is_box_selecting(&keys) || building_ghosts.is_empty() {
+        world_mouse_events.clear();
+        return;
+    }
+
+    for event in world_mouse_events.iter() {
+        let tiles = match event {
+            WorldMouseEvent::Click { button, position }
+                if input_map.is_button(Action::Place, *button) =>
+            {
+                vec![TilePosition::from_world(*position)]
+            }
+
+            WorldMouseEvent::DragEnd {
+                button,
+                start_world,
+                end_world,
+            } if input_map.is_button(Action::Place, *button) => {
+                TilePosition::from_world(*start_world).line_to(TilePosition::from_world(*end_world))
+            }
+
+            _ => continue,
+        };
+
+        for tile_position in tiles {
+            if let Some(entities) = tile_tracked_entities.get_entities_in_tile(tile_position) {
+                for entity in entities {
+                    if let Ok(mut placed_machine) = machines.get_mut(*entity) {
+                        placed_machine.enabled = !placed_machine.enabled;
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct ToolbarButtonSelectedEvent(pub Option<Entity>);
 
 #[derive(Component)]
 pub struct MoneyDisplay;
 
+#[derive(Component)]
+pub struct IncomeDisplay;
+
+#[derive(Component)]
+pub struct IncomeButton;
+
+#[derive(Component)]
+pub struct PauseLabel;
+
+#[derive(Component)]
+pub struct MergingLabel;
+
+/// Container of the toolbar buttons, in the order they are shown.
+#[derive(Component)]
+pub struct Toolbar;
+
 #[derive(Component)]
 pub struct MachineIcon(pub Machine);
 
@@ -658,6 +1368,37 @@ impl Default for ToolbarButton {
 #[derive(Component)]
 pub struct ToolbarButtonDelete;
 
+#[derive(Component)]
+pub struct ToolbarButtonToggle;
+
+#[derive(Component)]
+pub struct ToolbarButtonCopy;
+
+#[derive(Component)]
+pub struct ToolbarButtonPaste;
+
+#[derive(Component)]
+pub struct ToolIcon;
+
+#[derive(Component)]
+pub struct RefundLabel;
+
+#[derive(Component)]
+pub struct PlacementLabel;
+
+/// Tiles of the current drag and the machines to be placed on them.
+/// Deletion previews have no machines.
+#[derive(Component)]
+pub struct DragPreview {
+    plan: Vec<(TilePosition, Option<Machine>)>,
+}
+
+#[derive(Component)]
+pub struct DragPreviewTile(TilePosition);
+
+#[derive(Component)]
+pub struct DragPreviewCost;
+
 #[derive(Component)]
 pub struct ToolGhost {
     start_tile: TilePosition,
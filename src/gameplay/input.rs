@@ -1,13 +1,100 @@
 use bevy::{
-    input::mouse::{MouseScrollUnit, MouseWheel},
+    input::{
+        mouse::{MouseScrollUnit, MouseWheel},
+        touch::{TouchInput, TouchPhase},
+    },
     math::vec3,
     prelude::*,
+    ui::{CalculatedClip, FocusPolicy, UiStack},
+    utils::HashMap,
 };
 
 use crate::WorldInteraction;
 
-const CLICK_DURATION: f64 = 0.2;
-const CLICK_DISTANCE: f32 = 10.0;
+use super::{
+    gamepad::VirtualCursor,
+    hud::ToolGhost,
+    input_map::{Action, InputMap},
+    machines::PlacedMachine,
+    settings::Settings,
+    TILE_SIZE,
+};
+
+/// Rate at which the zoom closes in on its target, per second.
+const ZOOM_EASING: f32 = 15.0;
+
+/// Window pixels per second when panning with keys or the window edges.
+const KEY_PAN_SPEED: f32 = 1000.0;
+const EDGE_SCROLL_MARGIN: f32 = 16.0;
+
+/// Arrow keys pan in addition to the keys bound to the pan actions.
+const PAN_DIRECTIONS: [(Action, KeyCode, Vec2); 4] = [
+    (Action::PanUp, KeyCode::Up, Vec2::Y),
+    (Action::PanDown, KeyCode::Down, Vec2::NEG_Y),
+    (Action::PanLeft, KeyCode::Left, Vec2::NEG_X),
+    (Action::PanRight, KeyCode::Right, Vec2::X),
+];
+
+type UiNodes<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Node,
+        &'static GlobalTransform,
+        Option<&'static FocusPolicy>,
+        Option<&'static ComputedVisibility>,
+        Option<&'static CalculatedClip>,
+        Option<&'static WorldInteraction>,
+    ),
+>;
+
+/// Whether a UI node above the world catches the window position,
+/// going through the nodes from the top like the UI focus does for the mouse.
+fn is_over_ui(
+    position_window: Vec2,
+    window_height: f32,
+    ui_stack: &UiStack,
+    ui_nodes: &UiNodes,
+) -> bool {
+    // UI nodes are laid out from the top of the window
+    let position = Vec2::new(position_window.x, window_height - position_window.y);
+
+    for entity in ui_stack.uinodes.iter().rev() {
+        let (node, transform, focus_policy, visibility, clip, world) =
+            if let Ok(ui_node) = ui_nodes.get(*entity) {
+                ui_node
+            } else {
+                continue;
+            };
+
+        if visibility.is_some_and(|visibility| !visibility.is_visible()) {
+            continue;
+        }
+
+        let center = transform.translation().truncate();
+        let mut min = center - node.size() / 2.0;
+        let mut max = center + node.size() / 2.0;
+
+        if let Some(clip) = clip {
+            min = min.max(clip.clip.min);
+            max = max.min(clip.clip.max);
+        }
+
+        if !(min.x..max.x).contains(&position.x) || !(min.y..max.y).contains(&position.y) {
+            continue;
+        }
+
+        if world.is_some() {
+            return false;
+        }
+
+        if focus_policy.copied().unwrap_or(FocusPolicy::Block) == FocusPolicy::Block {
+            return true;
+        }
+    }
+
+    false
+}
 
 pub fn handle_bg_input(
     mut world_mouse: ResMut<WorldMouse>,
@@ -15,7 +102,14 @@ pub fn handle_bg_input(
     bg_inter: Query<&Interaction, With<WorldInteraction>>,
     camera: Query<(&Camera, &GlobalTransform)>,
     buttons: Res<Input<MouseButton>>,
+    virtual_cursor: Res<VirtualCursor>,
+    touch_pointer: Res<TouchPointer>,
+    ui_stack: Res<UiStack>,
+    ui_nodes: UiNodes,
     windows: Res<Windows>,
+    settings: Res<Settings>,
+    input_map: Res<InputMap>,
+    building_ghosts: Query<&ToolGhost>,
     mut world_mouse_events: EventWriter<WorldMouseEvent>,
 ) {
     let (camera, camera_global_transform) = camera.single();
@@ -26,8 +120,13 @@ pub fn handle_bg_input(
         return;
     };
 
-    let cursor_position_window = if let Some(position) = window.cursor_position() {
-        position
+    // Touches and the gamepad driven cursor replace the mouse while they are used
+    let (cursor_position_window, buttons) = if touch_pointer.active {
+        (touch_pointer.position_window, &touch_pointer.buttons)
+    } else if virtual_cursor.active {
+        (virtual_cursor.position_window, &virtual_cursor.buttons)
+    } else if let Some(position) = window.cursor_position() {
+        (position, &*buttons)
     } else {
         return;
     };
@@ -47,49 +146,67 @@ pub fn handle_bg_input(
 
     world_mouse.position_world = cursor_position_world;
 
-    for interaction in bg_inter.iter() {
-        match interaction {
-            Interaction::Hovered | Interaction::Clicked => {
-                let mouse_state = MouseButtonState::Pressed {
-                    time: time.elapsed_seconds_f64(),
-                    position_window: cursor_position_window_relative_to_center,
-                    position_world: cursor_position_world,
-                };
+    // Touch gestures check the UI when they start. The UI focus only follows the real mouse,
+    // so the virtual cursor looks for the UI under it by itself.
+    let hovering_world = if touch_pointer.active {
+        true
+    } else if virtual_cursor.active {
+        !is_over_ui(
+            cursor_position_window,
+            window.height(),
+            &ui_stack,
+            &ui_nodes,
+        )
+    } else {
+        bg_inter
+            .iter()
+            .any(|interaction| *interaction != Interaction::None)
+    };
 
-                if buttons.just_pressed(MouseButton::Left) {
-                    world_mouse.button_state_left = mouse_state;
-                }
+    if hovering_world {
+        let mouse_state = MouseButtonState::Pressed {
+            time: time.elapsed_seconds_f64(),
+            position_window: cursor_position_window_relative_to_center,
+            position_world: cursor_position_world,
+        };
 
-                if buttons.just_pressed(MouseButton::Middle) {
-                    world_mouse.button_state_middle = mouse_state;
-                }
+        if buttons.just_pressed(MouseButton::Left) {
+            world_mouse.button_state_left = mouse_state;
+        }
 
-                if buttons.just_pressed(MouseButton::Right) {
-                    world_mouse.button_state_right = mouse_state;
-                }
+        if buttons.just_pressed(MouseButton::Middle) {
+            world_mouse.button_state_middle = mouse_state;
+        }
 
-                match world_mouse.state {
-                    MouseState::None | MouseState::Hovering => {
-                        world_mouse_events.send(WorldMouseEvent::Hover {
-                            position: cursor_position_world,
-                        });
+        if buttons.just_pressed(MouseButton::Right) {
+            world_mouse.button_state_right = mouse_state;
+        }
 
-                        world_mouse.state = MouseState::Hovering;
-                    }
+        match world_mouse.state {
+            MouseState::None | MouseState::Hovering => {
+                world_mouse_events.send(WorldMouseEvent::Hover {
+                    position: cursor_position_world,
+                });
 
-                    _ => {}
-                }
+                world_mouse.state = MouseState::Hovering;
             }
 
-            Interaction::None => {
-                world_mouse.state = MouseState::None;
-                world_mouse.button_state_left = MouseButtonState::None;
-                world_mouse.button_state_middle = MouseButtonState::None;
-                world_mouse.button_state_right = MouseButtonState::None;
-            }
+            _ => {}
         }
+    } else {
+        world_mouse.state = MouseState::None;
+        world_mouse.button_state_left = MouseButtonState::None;
+        world_mouse.button_state_middle = MouseButtonState::None;
+        world_mouse.button_state_right = MouseButtonState::None;
     }
 
+    // Holding to click would keep trying to place the tool in the same spot
+    let repeats = |button| {
+        settings.hold_to_click
+            && building_ghosts.is_empty()
+            && input_map.is_button(Action::Place, button)
+    };
+
     let mouse_states = vec![
         update_mouse_button_state(
             MouseButton::Left,
@@ -97,7 +214,9 @@ pub fn handle_bg_input(
             cursor_position_window_relative_to_center,
             camera_global_transform,
             &time,
-            &buttons,
+            buttons,
+            &settings,
+            repeats(MouseButton::Left),
             &mut world_mouse_events,
         ),
         update_mouse_button_state(
@@ -106,7 +225,9 @@ pub fn handle_bg_input(
             cursor_position_window_relative_to_center,
             camera_global_transform,
             &time,
-            &buttons,
+            buttons,
+            &settings,
+            repeats(MouseButton::Middle),
             &mut world_mouse_events,
         ),
         update_mouse_button_state(
@@ -115,7 +236,9 @@ pub fn handle_bg_input(
             cursor_position_window_relative_to_center,
             camera_global_transform,
             &time,
-            &buttons,
+            buttons,
+            &settings,
+            repeats(MouseButton::Right),
             &mut world_mouse_events,
         ),
     ];
@@ -130,6 +253,8 @@ fn update_mouse_button_state(
     camera_global_transform: &GlobalTransform,
     time: &Res<Time>,
     buttons: &Input<MouseButton>,
+    settings: &Settings,
+    repeats: bool,
     world_mouse_events: &mut EventWriter<WorldMouseEvent>,
 ) -> MouseState {
     let camera_transform = camera_global_transform.compute_transform();
@@ -141,7 +266,13 @@ fn update_mouse_button_state(
     };
 
     match (buttons.just_released(button), *button_state) {
-        (released, MouseButtonState::Dragging { last_position }) => {
+        (
+            released,
+            MouseButtonState::Dragging {
+                last_position,
+                start_world,
+            },
+        ) => {
             world_mouse_events.send(WorldMouseEvent::Drag {
                 button,
                 start_world: screen_to_world(last_position),
@@ -149,12 +280,19 @@ fn update_mouse_button_state(
             });
 
             if released {
+                world_mouse_events.send(WorldMouseEvent::DragEnd {
+                    button,
+                    start_world,
+                    end_world: screen_to_world(cursor_position_window),
+                });
+
                 *button_state = MouseButtonState::None;
 
                 MouseState::None
             } else {
                 *button_state = MouseButtonState::Dragging {
                     last_position: cursor_position_window,
+                    start_world,
                 };
 
                 MouseState::Dragging
@@ -171,8 +309,8 @@ fn update_mouse_button_state(
         ) => {
             let press_duration = time.elapsed_seconds_f64() - press_time;
             let press_distance = cursor_position_window.distance(position_window);
-            let suitable_for_click =
-                press_duration < CLICK_DURATION && press_distance < CLICK_DISTANCE;
+            let still = press_distance < settings.click_distance;
+            let suitable_for_click = press_duration < settings.click_duration && still;
 
             if released {
                 if suitable_for_click {
@@ -186,10 +324,26 @@ fn update_mouse_button_state(
                         start_world: screen_to_world(cursor_position_window),
                         end_world: screen_to_world(position_window),
                     });
+                    world_mouse_events.send(WorldMouseEvent::DragEnd {
+                        button,
+                        start_world: position_world,
+                        end_world: screen_to_world(cursor_position_window),
+                    });
                 }
 
                 *button_state = MouseButtonState::None;
 
+                MouseState::None
+            } else if !suitable_for_click && still && repeats {
+                world_mouse_events.send(WorldMouseEvent::Click {
+                    button,
+                    position: position_world,
+                });
+
+                *button_state = MouseButtonState::Repeating {
+                    next_time: time.elapsed_seconds_f64() + 1.0 / settings.hold_click_rate,
+                };
+
                 MouseState::None
             } else if !suitable_for_click {
                 world_mouse_events.send(WorldMouseEvent::Drag {
@@ -200,6 +354,7 @@ fn update_mouse_button_state(
 
                 *button_state = MouseButtonState::Dragging {
                     last_position: cursor_position_window,
+                    start_world: position_world,
                 };
 
                 MouseState::Dragging
@@ -208,48 +363,326 @@ fn update_mouse_button_state(
             }
         }
 
+        // Clicks follow the cursor while the button stays held
+        (released, MouseButtonState::Repeating { next_time }) => {
+            let now = time.elapsed_seconds_f64();
+
+            if released {
+                *button_state = MouseButtonState::None;
+            } else if now >= next_time {
+                world_mouse_events.send(WorldMouseEvent::Click {
+                    button,
+                    position: screen_to_world(cursor_position_window),
+                });
+
+                *button_state = MouseButtonState::Repeating {
+                    next_time: (next_time + 1.0 / settings.hold_click_rate).max(now),
+                };
+            }
+
+            MouseState::None
+        }
+
         _ => MouseState::None,
     }
 }
 
+/// Turns touches into presses of the button bound to placing: a tap clicks and
+/// one finger drags like the mouse does. Two fingers pan and pinch to zoom the camera instead.
+pub fn handle_touch_input(
+    mut touch_events: EventReader<TouchInput>,
+    mut touch_pointer: ResMut<TouchPointer>,
+    time: Res<Time>,
+    input_map: Res<InputMap>,
+    windows: Res<Windows>,
+    bg_inter: Query<&Interaction, With<WorldInteraction>>,
+    settings: Res<Settings>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    let window_height = if let Some(window) = windows.get_primary() {
+        window.height()
+    } else {
+        return;
+    };
+
+    // UI focus follows the first finger, so this tells where a new touch has landed
+    let over_ui = bg_inter
+        .iter()
+        .any(|interaction| *interaction == Interaction::None);
+
+    let camera_move = touch_pointer.update(
+        touch_events.iter(),
+        window_height,
+        time.elapsed_seconds_f64(),
+        over_ui,
+        input_map.button(Action::Place),
+        &settings,
+    );
+
+    if let Some(camera_move) = camera_move {
+        let mut camera_transform = camera.single_mut();
+        let scale = camera_transform.scale.x;
+
+        camera_transform.translation -= (camera_move.pan * scale).extend(0.0);
+        camera_transform.scale = settings
+            .clamp_zoom(camera_transform.scale * vec3(camera_move.zoom, camera_move.zoom, 1.0));
+    }
+}
+
 pub fn drag_camera(
     mut camera: Query<&mut Transform, With<Camera2d>>,
     mut world_mouse_events: EventReader<WorldMouseEvent>,
+    input_map: Res<InputMap>,
 ) {
     let mut camera_transform = camera.single_mut();
 
     for event in world_mouse_events.iter() {
         match event {
             WorldMouseEvent::Drag {
-                button: MouseButton::Right,
+                button,
                 start_world,
                 end_world,
                 ..
-            } => camera_transform.translation -= (*end_world - *start_world).extend(0.0),
+            } if input_map.is_button(Action::Pan, *button) => {
+                camera_transform.translation -= (*end_world - *start_world).extend(0.0)
+            }
 
             _ => (),
         }
     }
 }
 
+/// Pans with the bound pan keys and the arrow keys. The speed is in window pixels,
+/// so the view moves equally fast at every zoom level.
+pub fn pan_camera_with_keys(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    time: Res<Time>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if ctrl {
+        return;
+    }
+
+    let mut direction = Vec2::ZERO;
+
+    for (action, arrow, step) in PAN_DIRECTIONS {
+        if input_map.pressed(action, &keys) || keys.pressed(arrow) {
+            direction += step;
+        }
+    }
+
+    pan_camera(&mut camera.single_mut(), direction, time.delta_seconds());
+}
+
+/// Pans while the cursor rests on an edge of the focused window, if enabled in the settings.
+pub fn scroll_camera_at_edges(
+    settings: Res<Settings>,
+    windows: Res<Windows>,
+    time: Res<Time>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    if !settings.edge_scrolling {
+        return;
+    }
+
+    let (window, cursor_position) = match windows.get_primary() {
+        Some(window) if window.is_focused() => match window.cursor_position() {
+            Some(cursor_position) => (window, cursor_position),
+            None => return,
+        },
+        _ => return,
+    };
+
+    let mut direction = Vec2::ZERO;
+
+    if cursor_position.x < EDGE_SCROLL_MARGIN {
+        direction.x -= 1.0;
+    } else if cursor_position.x > window.width() - EDGE_SCROLL_MARGIN {
+        direction.x += 1.0;
+    }
+
+    if cursor_position.y < EDGE_SCROLL_MARGIN {
+        direction.y -= 1.0;
+    } else if cursor_position.y > window.height() - EDGE_SCROLL_MARGIN {
+        direction.y += 1.0;
+    }
+
+    pan_camera(&mut camera.single_mut(), direction, time.delta_seconds());
+}
+
+fn pan_camera(camera_transform: &mut Transform, direction: Vec2, delta_seconds: f32) {
+    if direction == Vec2::ZERO {
+        return;
+    }
+
+    let scale = camera_transform.scale.x;
+
+    camera_transform.translation +=
+        (direction.normalize() * KEY_PAN_SPEED * scale * delta_seconds).extend(0.0);
+}
+
+/// Centers the camera on the bounding box of the placed machines,
+/// zooming out when they do not fit in the window.
+pub fn recenter_camera(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    windows: Res<Windows>,
+    settings: Res<Settings>,
+    machines: Query<&Transform, (With<PlacedMachine>, Without<Camera2d>)>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    if !input_map.just_pressed(Action::Recenter, &keys) {
+        return;
+    }
+
+    let window = if let Some(window) = windows.get_primary() {
+        window
+    } else {
+        return;
+    };
+
+    let positions = machines
+        .iter()
+        .map(|transform| transform.translation.truncate());
+
+    let mut camera_transform = camera.single_mut();
+    let (center, scale) = frame_positions(positions, window, camera_transform.scale.x, &settings);
+
+    camera_transform.translation = center.extend(camera_transform.translation.z);
+    camera_transform.scale = vec3(scale, scale, 1.0);
+}
+
+/// Center and zoom showing all the positions in the window. The zoom only changes
+/// from the given one when it is too close to fit them all.
+pub fn frame_positions(
+    positions: impl IntoIterator<Item = Vec2>,
+    window: &Window,
+    scale: f32,
+    settings: &Settings,
+) -> (Vec2, f32) {
+    let mut positions = positions.into_iter();
+
+    // With nothing built yet, go back to where the game starts
+    let (min, max) = match positions.next() {
+        Some(first) => positions.fold((first, first), |(min, max), position| {
+            (min.min(position), max.max(position))
+        }),
+        None => (Vec2::ZERO, Vec2::ZERO),
+    };
+
+    let size = max - min + Vec2::splat(TILE_SIZE);
+    let fitting_scale = (size.x / window.width()).max(size.y / window.height());
+
+    let scale = if scale < fitting_scale {
+        settings.clamp_zoom(Vec3::splat(fitting_scale)).x
+    } else {
+        scale
+    };
+
+    ((min + max) / 2.0, scale)
+}
+
+/// Scrolling and the zoom keys move the zoom target, which the camera eases towards.
+/// The wheel zooms around the world point under the cursor, keys zoom around the center.
 pub fn zoom_camera(
     mut camera: Query<&mut Transform, With<Camera2d>>,
     mut scroll_events: EventReader<MouseWheel>,
+    mut camera_zoom: ResMut<CameraZoom>,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    settings: Res<Settings>,
+    windows: Res<Windows>,
+    time: Res<Time>,
 ) {
     let mut camera_transform = camera.single_mut();
+    let scale = camera_transform.scale.x;
+
+    // Zooming by other means, like pinching or recentering, moves the target along
+    if camera_zoom.current != scale {
+        camera_zoom.target = scale;
+        camera_zoom.anchor = None;
+    }
 
     let mut scroll = 1.0f32;
 
     for event in scroll_events.iter() {
         match event.unit {
-            MouseScrollUnit::Line => scroll -= event.y * 0.2,
-            MouseScrollUnit::Pixel => scroll -= (event.y / 100.0) * 0.2,
+            MouseScrollUnit::Line => scroll -= event.y * settings.line_zoom_sensitivity,
+            MouseScrollUnit::Pixel => scroll -= event.y * settings.pixel_zoom_sensitivity,
         }
     }
 
-    let scale_multiplier = vec3(scroll, scroll, 1.0);
-    camera_transform.scale = (camera_transform.scale * scale_multiplier)
-        .clamp(vec3(1.0, 1.0, 1.0), vec3(20.0, 20.0, 1.0));
+    let scrolled = scroll != 1.0;
+
+    if input_map.pressed(Action::ZoomIn, &keys) {
+        scroll -= 2.0 * time.delta_seconds();
+    }
+
+    if input_map.pressed(Action::ZoomOut, &keys) {
+        scroll += 2.0 * time.delta_seconds();
+    }
+
+    if scroll != 1.0 {
+        // A big flick of a touchpad must not flip the scale
+        let target = camera_zoom.target * scroll.max(0.1);
+        camera_zoom.target = settings.clamp_zoom(Vec3::splat(target)).x;
+
+        camera_zoom.anchor = windows
+            .get_primary()
+            .filter(|_| scrolled)
+            .and_then(|window| {
+                let window_size = Vec2::new(window.width(), window.height());
+
+                window
+                    .cursor_position()
+                    .map(|position| position - window_size / 2.0)
+            });
+    }
+
+    let eased = {
+        let eased = scale
+            + (camera_zoom.target - scale) * (1.0 - (-ZOOM_EASING * time.delta_seconds()).exp());
+
+        if (camera_zoom.target - eased).abs() < 0.001 {
+            camera_zoom.target
+        } else {
+            eased
+        }
+    };
+
+    if eased != scale {
+        // Keep the world point under the anchor where it is on the screen
+        if let Some(anchor) = camera_zoom.anchor {
+            camera_transform.translation += (anchor * (scale - eased)).extend(0.0);
+        }
+
+        camera_transform.scale = vec3(eased, eased, 1.0);
+    }
+
+    camera_zoom.current = eased;
+}
+
+/// Where the camera zoom is heading.
+#[derive(Resource, Copy, Clone, Debug)]
+pub struct CameraZoom {
+    pub target: f32,
+    /// Scale the camera was left at, to notice zooming done elsewhere.
+    pub current: f32,
+    /// Window position relative to the center that stays in place while zooming.
+    pub anchor: Option<Vec2>,
+}
+
+impl Default for CameraZoom {
+    fn default() -> Self {
+        CameraZoom {
+            target: 1.0,
+            current: 1.0,
+            anchor: None,
+        }
+    }
 }
 
 #[derive(Resource, Copy, Clone, Debug)]
@@ -261,6 +694,187 @@ pub struct WorldMouse {
     pub button_state_right: MouseButtonState,
 }
 
+/// Stands in for the mouse while a finger is on the world.
+#[derive(Resource, Default)]
+pub struct TouchPointer {
+    pub active: bool,
+    /// Same coordinates as `Window::cursor_position`.
+    pub position_window: Vec2,
+    pub buttons: Input<MouseButton>,
+    fingers: HashMap<u64, Vec2>,
+    gesture: TouchGesture,
+}
+
+impl TouchPointer {
+    /// Classifies the gesture the touch events of this frame belong to and presses
+    /// or releases the place button for it. Returns how two fingers moved the camera.
+    fn update<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = &'a TouchInput>,
+        window_height: f32,
+        now: f64,
+        over_ui: bool,
+        place_button: Option<MouseButton>,
+        settings: &Settings,
+    ) -> Option<TouchCameraMove> {
+        self.buttons.clear();
+
+        for event in events {
+            // Touch positions start at the top left corner, the cursor position at the bottom left
+            let position = Vec2::new(event.position.x, window_height - event.position.y);
+
+            match event.phase {
+                TouchPhase::Started => {
+                    self.fingers.insert(event.id, position);
+
+                    self.gesture = match (self.fingers.len(), self.gesture) {
+                        (1, _) => match place_button {
+                            Some(_) if !over_ui => TouchGesture::Pending {
+                                start_time: now,
+                                start: position,
+                            },
+                            _ => TouchGesture::Ignored,
+                        },
+
+                        (_, TouchGesture::Pressing(button)) => {
+                            self.buttons.release(button);
+                            TouchGesture::Camera { fingers: None }
+                        }
+
+                        (_, TouchGesture::Pending { .. }) => TouchGesture::Camera { fingers: None },
+
+                        (_, gesture) => gesture,
+                    };
+                }
+
+                TouchPhase::Moved => {
+                    if let Some(finger) = self.fingers.get_mut(&event.id) {
+                        *finger = position;
+                    }
+                }
+
+                TouchPhase::Ended | TouchPhase::Cancelled => {
+                    self.fingers.remove(&event.id);
+
+                    if !self.fingers.is_empty() {
+                        continue;
+                    }
+
+                    match self.gesture {
+                        TouchGesture::Pending { start, .. } if event.phase == TouchPhase::Ended => {
+                            if let Some(button) = place_button {
+                                self.position_window = start;
+                                self.buttons.press(button);
+                                self.buttons.release(button);
+                            }
+                        }
+
+                        TouchGesture::Pressing(button) => {
+                            self.position_window = position;
+                            self.buttons.release(button);
+                        }
+
+                        _ => {}
+                    }
+
+                    self.gesture = TouchGesture::None;
+                }
+            }
+        }
+
+        let single_finger = if self.fingers.len() == 1 {
+            self.fingers.values().next().copied()
+        } else {
+            None
+        };
+
+        let mut camera_move = None;
+
+        match (self.gesture, single_finger) {
+            // Holding still or moving away turns the touch into a drag starting where it began
+            (TouchGesture::Pending { start_time, start }, Some(position)) => {
+                let held = now - start_time >= settings.click_duration;
+                let moved = position.distance(start) >= settings.click_distance;
+
+                if let Some(button) = place_button.filter(|_| held || moved) {
+                    self.position_window = start;
+                    self.buttons.press(button);
+                    self.gesture = TouchGesture::Pressing(button);
+                } else {
+                    self.position_window = position;
+                }
+            }
+
+            (TouchGesture::Pressing(_), Some(position)) => {
+                self.position_window = position;
+            }
+
+            (TouchGesture::Camera { fingers }, _) => {
+                let mut ids = self.fingers.keys().copied().collect::<Vec<_>>();
+                ids.sort_unstable();
+
+                if let [first, second, ..] = ids[..] {
+                    let a = self.fingers[&first];
+                    let b = self.fingers[&second];
+                    let center = (a + b) / 2.0;
+                    let distance = a.distance(b).max(1.0);
+
+                    // Compare with the previous frame only while the same two fingers are down
+                    if let Some((last_ids, last_center, last_distance)) = fingers {
+                        if last_ids == (first, second) {
+                            camera_move = Some(TouchCameraMove {
+                                pan: center - last_center,
+                                zoom: last_distance / distance,
+                            });
+                        }
+                    }
+
+                    self.gesture = TouchGesture::Camera {
+                        fingers: Some(((first, second), center, distance)),
+                    };
+                }
+            }
+
+            _ => {}
+        }
+
+        // Stays active for the frame the last finger is lifted, so the release is seen
+        self.active = matches!(
+            self.gesture,
+            TouchGesture::Pending { .. } | TouchGesture::Pressing(_)
+        ) || self.buttons.get_just_released().len() > 0;
+
+        camera_move
+    }
+}
+
+/// How two fingers moved since the previous frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct TouchCameraMove {
+    /// Movement of the point between the fingers, in window pixels.
+    pan: Vec2,
+    /// Factor the camera scale is multiplied by.
+    zoom: f32,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+enum TouchGesture {
+    #[default]
+    None,
+    /// Started over the UI, or with nothing bound to placing.
+    Ignored,
+    /// Could still become a tap.
+    Pending {
+        start_time: f64,
+        start: Vec2,
+    },
+    Pressing(MouseButton),
+    /// Ids, center and distance of the two fingers in the previous frame.
+    Camera {
+        fingers: Option<((u64, u64), Vec2, f32)>,
+    },
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum MouseState {
     None,
@@ -290,9 +904,31 @@ pub enum MouseButtonState {
     },
     Dragging {
         last_position: Vec2,
+        start_world: Vec2,
+    },
+    /// Held long enough to click repeatedly.
+    Repeating {
+        next_time: f64,
     },
 }
 
+impl WorldMouse {
+    /// World position where the current drag gesture of the given button has started.
+    pub fn drag_start(&self, button: MouseButton) -> Option<Vec2> {
+        let button_state = match button {
+            MouseButton::Left => self.button_state_left,
+            MouseButton::Middle => self.button_state_middle,
+            MouseButton::Right => self.button_state_right,
+            _ => return None,
+        };
+
+        match button_state {
+            MouseButtonState::Dragging { start_world, .. } => Some(start_world),
+            _ => None,
+        }
+    }
+}
+
 impl Default for WorldMouse {
     fn default() -> Self {
         WorldMouse {
@@ -305,7 +941,7 @@ impl Default for WorldMouse {
     }
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 pub enum WorldMouseEvent {
     Click {
         button: MouseButton,
@@ -321,4 +957,197 @@ pub enum WorldMouseEvent {
         start_world: Vec2,
         end_world: Vec2,
     },
+
+    /// Sent once the button is released, spanning the whole drag gesture.
+    DragEnd {
+        button: MouseButton,
+        start_world: Vec2,
+        end_world: Vec2,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW_HEIGHT: f32 = 600.0;
+    const PLACE: MouseButton = MouseButton::Left;
+
+    fn touch(id: u64, phase: TouchPhase, x: f32, y: f32) -> TouchInput {
+        TouchInput {
+            phase,
+            position: Vec2::new(x, y),
+            force: None,
+            id,
+        }
+    }
+
+    fn update(
+        pointer: &mut TouchPointer,
+        events: &[TouchInput],
+        now: f64,
+    ) -> Option<TouchCameraMove> {
+        pointer.update(
+            events,
+            WINDOW_HEIGHT,
+            now,
+            false,
+            Some(PLACE),
+            &Settings::default(),
+        )
+    }
+
+    #[test]
+    fn tap_clicks_where_the_finger_landed() {
+        let mut pointer = TouchPointer::default();
+
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Started, 100.0, 100.0)],
+            0.0,
+        );
+        assert!(!pointer.buttons.pressed(PLACE));
+
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Ended, 102.0, 101.0)],
+            0.1,
+        );
+        assert!(pointer.buttons.just_pressed(PLACE));
+        assert!(pointer.buttons.just_released(PLACE));
+        assert_eq!(pointer.position_window, Vec2::new(100.0, 500.0));
+        assert!(pointer.active);
+
+        update(&mut pointer, &[], 0.2);
+        assert!(!pointer.active);
+    }
+
+    #[test]
+    fn moving_finger_drags_from_the_start() {
+        let mut pointer = TouchPointer::default();
+
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Started, 100.0, 100.0)],
+            0.0,
+        );
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Moved, 150.0, 100.0)],
+            0.05,
+        );
+        assert!(pointer.buttons.just_pressed(PLACE));
+        assert_eq!(pointer.position_window, Vec2::new(100.0, 500.0));
+
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Moved, 200.0, 100.0)],
+            0.1,
+        );
+        assert!(pointer.buttons.pressed(PLACE));
+        assert_eq!(pointer.position_window, Vec2::new(200.0, 500.0));
+
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Ended, 220.0, 100.0)],
+            0.15,
+        );
+        assert!(pointer.buttons.just_released(PLACE));
+        assert!(!pointer.buttons.just_pressed(PLACE));
+        assert_eq!(pointer.position_window, Vec2::new(220.0, 500.0));
+    }
+
+    #[test]
+    fn holding_still_starts_a_drag() {
+        let mut pointer = TouchPointer::default();
+
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Started, 100.0, 100.0)],
+            0.0,
+        );
+        update(&mut pointer, &[], 0.1);
+        assert!(!pointer.buttons.pressed(PLACE));
+
+        update(&mut pointer, &[], 0.3);
+        assert!(pointer.buttons.just_pressed(PLACE));
+    }
+
+    #[test]
+    fn touch_over_ui_is_ignored() {
+        let mut pointer = TouchPointer::default();
+        let settings = Settings::default();
+        let started = [touch(0, TouchPhase::Started, 100.0, 100.0)];
+        let ended = [touch(0, TouchPhase::Ended, 100.0, 100.0)];
+
+        pointer.update(&started, WINDOW_HEIGHT, 0.0, true, Some(PLACE), &settings);
+        pointer.update(&ended, WINDOW_HEIGHT, 0.1, true, Some(PLACE), &settings);
+
+        assert!(!pointer.buttons.just_pressed(PLACE));
+        assert!(!pointer.active);
+    }
+
+    #[test]
+    fn two_fingers_pinch_to_zoom() {
+        let mut pointer = TouchPointer::default();
+
+        let started = [
+            touch(0, TouchPhase::Started, 100.0, 300.0),
+            touch(1, TouchPhase::Started, 200.0, 300.0),
+        ];
+        assert_eq!(update(&mut pointer, &started, 0.0), None);
+
+        let spread = [touch(1, TouchPhase::Moved, 300.0, 300.0)];
+        let camera_move = update(&mut pointer, &spread, 0.05).unwrap();
+
+        assert_eq!(camera_move.zoom, 0.5);
+        assert_eq!(camera_move.pan, Vec2::new(50.0, 0.0));
+        assert!(!pointer.buttons.pressed(PLACE));
+        assert!(!pointer.active);
+    }
+
+    #[test]
+    fn two_fingers_pan() {
+        let mut pointer = TouchPointer::default();
+
+        let started = [
+            touch(0, TouchPhase::Started, 100.0, 300.0),
+            touch(1, TouchPhase::Started, 200.0, 300.0),
+        ];
+        update(&mut pointer, &started, 0.0);
+
+        let moved = [
+            touch(0, TouchPhase::Moved, 100.0, 340.0),
+            touch(1, TouchPhase::Moved, 200.0, 340.0),
+        ];
+        let camera_move = update(&mut pointer, &moved, 0.05).unwrap();
+
+        assert_eq!(camera_move.zoom, 1.0);
+        assert_eq!(camera_move.pan, Vec2::new(0.0, -40.0));
+    }
+
+    #[test]
+    fn second_finger_ends_a_drag() {
+        let mut pointer = TouchPointer::default();
+
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Started, 100.0, 100.0)],
+            0.0,
+        );
+        update(
+            &mut pointer,
+            &[touch(0, TouchPhase::Moved, 150.0, 100.0)],
+            0.05,
+        );
+        assert!(pointer.buttons.pressed(PLACE));
+
+        update(
+            &mut pointer,
+            &[touch(1, TouchPhase::Started, 300.0, 100.0)],
+            0.1,
+        );
+        assert!(pointer.buttons.just_released(PLACE));
+        assert!(!pointer.buttons.pressed(PLACE));
+    }
 }
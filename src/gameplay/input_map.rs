@@ -0,0 +1,563 @@
+use std::{collections::HashMap, io};
+
+use bevy::prelude::*;
+
+use super::{
+    input::WorldMouse,
+    settings::{read_text_file, write_text_file},
+};
+
+const INPUT_MAP_FILE: &str = "input.cfg";
+
+/// Keys that can be bound to actions, also used to read and write their names.
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Escape,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::Delete,
+    KeyCode::End,
+    KeyCode::PageDown,
+    KeyCode::PageUp,
+    KeyCode::Return,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Minus,
+    KeyCode::Equals,
+    KeyCode::LBracket,
+    KeyCode::RBracket,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Backslash,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::Grave,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::NumpadAdd,
+    KeyCode::NumpadSubtract,
+];
+
+/// Keys with a fixed meaning that cannot be bound to actions.
+const RESERVED_KEYS: &[KeyCode] = &[
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+    KeyCode::Left,
+    KeyCode::Up,
+    KeyCode::Right,
+    KeyCode::Down,
+    KeyCode::Back,
+    KeyCode::F1,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::LShift,
+    KeyCode::RShift,
+];
+
+/// Mouse buttons tracked by the world mouse.
+const BINDABLE_BUTTONS: &[MouseButton] =
+    &[MouseButton::Left, MouseButton::Middle, MouseButton::Right];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Place,
+    Pan,
+    Cancel,
+    Pick,
+    HoldGhost,
+    Rotate,
+    ZoomIn,
+    ZoomOut,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    Recenter,
+    ToggleMerging,
+    Toggle,
+    ToggleGrid,
+    DeleteSelection,
+    DeleteTool,
+    Pause,
+    Copy,
+    Undo,
+    Redo,
+    ExportBlueprint,
+    ImportBlueprint,
+}
+
+impl Action {
+    pub fn list() -> &'static [Action] {
+        use Action::*;
+
+        &[
+            Place,
+            Pan,
+            Cancel,
+            Pick,
+            HoldGhost,
+            Rotate,
+            ZoomIn,
+            ZoomOut,
+            PanUp,
+            PanDown,
+            PanLeft,
+            PanRight,
+            Recenter,
+            ToggleMerging,
+            Toggle,
+            ToggleGrid,
+            DeleteSelection,
+            DeleteTool,
+            Pause,
+            Copy,
+            Undo,
+            Redo,
+            ExportBlueprint,
+            ImportBlueprint,
+        ]
+    }
+
+    pub fn name(&self) -> &str {
+        use Action::*;
+
+        match self {
+            Place => "Click / Place",
+            Pan => "Pan camera",
+            Cancel => "Cancel",
+            Pick => "Pick machine",
+            HoldGhost => "Hold ghost",
+            Rotate => "Rotate",
+            ZoomIn => "Zoom in",
+            ZoomOut => "Zoom out",
+            PanUp => "Pan up",
+            PanDown => "Pan down",
+            PanLeft => "Pan left",
+            PanRight => "Pan right",
+            Recenter => "Recenter on factory",
+            ToggleMerging => "Toggle coin merging",
+            Toggle => "Turn machines on or off",
+            ToggleGrid => "Toggle grid",
+            DeleteSelection => "Delete selection",
+            DeleteTool => "Delete tool",
+            Pause => "Pause",
+            Copy => "Copy selection (Ctrl)",
+            Undo => "Undo (Ctrl)",
+            Redo => "Redo (Ctrl)",
+            ExportBlueprint => "Export blueprint (Ctrl)",
+            ImportBlueprint => "Import blueprint (Ctrl)",
+        }
+    }
+
+    /// Name of the action in the settings file.
+    pub fn id(&self) -> &str {
+        use Action::*;
+
+        match self {
+            Place => "place",
+            Pan => "pan",
+            Cancel => "cancel",
+            Pick => "pick",
+            HoldGhost => "hold_ghost",
+            Rotate => "rotate",
+            ZoomIn => "zoom_in",
+            ZoomOut => "zoom_out",
+            PanUp => "pan_up",
+            PanDown => "pan_down",
+            PanLeft => "pan_left",
+            PanRight => "pan_right",
+            Recenter => "recenter",
+            ToggleMerging => "toggle_merging",
+            Toggle => "toggle",
+            ToggleGrid => "toggle_grid",
+            DeleteSelection => "delete_selection",
+            DeleteTool => "delete_tool",
+            Pause => "pause",
+            Copy => "copy",
+            Undo => "undo",
+            Redo => "redo",
+            ExportBlueprint => "export_blueprint",
+            ImportBlueprint => "import_blueprint",
+        }
+    }
+
+    /// Actions performed with mouse gestures in the world.
+    pub fn accepts_mouse(&self) -> bool {
+        use Action::*;
+
+        matches!(self, Place | Pan | Cancel | Pick | HoldGhost)
+    }
+
+    /// Dragging with a button and clicking it without moving are told apart,
+    /// so panning and cancelling, or holding a ghost and picking, can use the same button.
+    pub fn shares_button_with(&self, other: Action) -> bool {
+        use Action::*;
+
+        matches!(
+            (self, other),
+            (Pan, Cancel) | (Cancel, Pan) | (HoldGhost, Pick) | (Pick, HoldGhost)
+        )
+    }
+
+    /// Everything but dragging in the world can be done with a key.
+    pub fn accepts_key(&self) -> bool {
+        use Action::*;
+
+        !matches!(self, Place | Pan | HoldGhost)
+    }
+}
+
+/// An action can be bound to one mouse button and one key at the same time.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ActionBinding {
+    pub mouse: Option<MouseButton>,
+    pub key: Option<KeyCode>,
+}
+
+impl ActionBinding {
+    fn new(mouse: Option<MouseButton>, key: Option<KeyCode>) -> ActionBinding {
+        ActionBinding { mouse, key }
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.mouse, self.key) {
+            (Some(button), Some(key)) => format!("{:?} mouse, {:?}", button, key),
+            (Some(button), None) => format!("{:?} mouse", button),
+            (None, Some(key)) => format!("{:?}", key),
+            (None, None) => "Unbound".to_string(),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub struct InputMap {
+    bindings: HashMap<Action, ActionBinding>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Action::*;
+
+        let bindings = [
+            (Place, ActionBinding::new(Some(MouseButton::Left), None)),
+            (Pan, ActionBinding::new(Some(MouseButton::Right), None)),
+            (
+                Cancel,
+                ActionBinding::new(Some(MouseButton::Right), Some(KeyCode::Escape)),
+            ),
+            (
+                Pick,
+                ActionBinding::new(Some(MouseButton::Middle), Some(KeyCode::Q)),
+            ),
+            (
+                HoldGhost,
+                ActionBinding::new(Some(MouseButton::Middle), None),
+            ),
+            (Rotate, ActionBinding::new(None, Some(KeyCode::R))),
+            (ZoomIn, ActionBinding::new(None, Some(KeyCode::Equals))),
+            (ZoomOut, ActionBinding::new(None, Some(KeyCode::Minus))),
+            (PanUp, ActionBinding::new(None, Some(KeyCode::W))),
+            (PanDown, ActionBinding::new(None, Some(KeyCode::S))),
+            (PanLeft, ActionBinding::new(None, Some(KeyCode::A))),
+            (PanRight, ActionBinding::new(None, Some(KeyCode::D))),
+            (Recenter, ActionBinding::new(None, Some(KeyCode::Home))),
+            (ToggleMerging, ActionBinding::new(None, Some(KeyCode::M))),
+            (Toggle, ActionBinding::new(None, Some(KeyCode::T))),
+            (ToggleGrid, ActionBinding::new(None, Some(KeyCode::G))),
+            (
+                DeleteSelection,
+                ActionBinding::new(None, Some(KeyCode::Delete)),
+            ),
+            (DeleteTool, ActionBinding::new(None, Some(KeyCode::X))),
+            (Pause, ActionBinding::new(None, Some(KeyCode::Space))),
+            (Copy, ActionBinding::new(None, Some(KeyCode::C))),
+            (Undo, ActionBinding::new(None, Some(KeyCode::Z))),
+            (Redo, ActionBinding::new(None, Some(KeyCode::Y))),
+            (ExportBlueprint, ActionBinding::new(None, Some(KeyCode::E))),
+            (ImportBlueprint, ActionBinding::new(None, Some(KeyCode::I))),
+        ];
+
+        InputMap {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl InputMap {
+    pub fn binding(&self, action: Action) -> ActionBinding {
+        self.bindings.get(&action).copied().unwrap_or_default()
+    }
+
+    /// Takes the button away from the other actions, except from the one it shares a gesture with.
+    pub fn bind_mouse(&mut self, action: Action, button: MouseButton) {
+        if action.accepts_mouse() && BINDABLE_BUTTONS.contains(&button) {
+            for (other, binding) in self.bindings.iter_mut() {
+                if binding.mouse == Some(button) && !action.shares_button_with(*other) {
+                    binding.mouse = None;
+                }
+            }
+
+            self.bindings.entry(action).or_default().mouse = Some(button);
+        }
+    }
+
+    /// Takes the key away from the other actions.
+    pub fn bind_key(&mut self, action: Action, key: KeyCode) {
+        if action.accepts_key() && BINDABLE_KEYS.contains(&key) {
+            for binding in self.bindings.values_mut() {
+                if binding.key == Some(key) {
+                    binding.key = None;
+                }
+            }
+
+            self.bindings.entry(action).or_default().key = Some(key);
+        }
+    }
+
+    /// Mouse button performing the action, if any.
+    pub fn button(&self, action: Action) -> Option<MouseButton> {
+        self.binding(action).mouse
+    }
+
+    /// Whether world mouse events of the button perform the action.
+    pub fn is_button(&self, action: Action, button: MouseButton) -> bool {
+        self.button(action) == Some(button)
+    }
+
+    /// World position where the current drag gesture of the action has started.
+    pub fn drag_start(&self, action: Action, world_mouse: &WorldMouse) -> Option<Vec2> {
+        self.button(action)
+            .and_then(|button| world_mouse.drag_start(button))
+    }
+
+    pub fn just_pressed(&self, action: Action, keys: &Input<KeyCode>) -> bool {
+        self.binding(action)
+            .key
+            .is_some_and(|key| keys.just_pressed(key))
+    }
+
+    pub fn pressed(&self, action: Action, keys: &Input<KeyCode>) -> bool {
+        self.binding(action)
+            .key
+            .is_some_and(|key| keys.pressed(key))
+    }
+
+    pub fn is_bindable_key(key: KeyCode) -> bool {
+        BINDABLE_KEYS.contains(&key)
+    }
+
+    pub fn is_bindable_button(button: MouseButton) -> bool {
+        BINDABLE_BUTTONS.contains(&button)
+    }
+
+    /// Keys with a name that can be looked up.
+    pub fn is_named_key(key: KeyCode) -> bool {
+        BINDABLE_KEYS.contains(&key) || RESERVED_KEYS.contains(&key)
+    }
+
+    /// Looks up a key used by the game from its `Debug` name.
+    pub fn key_from_name(name: &str) -> Option<KeyCode> {
+        BINDABLE_KEYS
+            .iter()
+            .chain(RESERVED_KEYS)
+            .copied()
+            .find(|key| format!("{:?}", key) == name)
+    }
+
+    /// Looks up a button tracked by the world mouse from its `Debug` name.
+    pub fn button_from_name(name: &str) -> Option<MouseButton> {
+        BINDABLE_BUTTONS
+            .iter()
+            .copied()
+            .find(|button| format!("{:?}", button) == name)
+    }
+
+    /// One line per action, e.g. `cancel = mouse:Right key:Escape`.
+    pub fn encode(&self) -> String {
+        let mut encoded = String::new();
+
+        for action in Action::list() {
+            let binding = self.binding(*action);
+
+            encoded.push_str(action.id());
+            encoded.push_str(" =");
+
+            if let Some(button) = binding.mouse {
+                encoded.push_str(&format!(" mouse:{:?}", button));
+            }
+
+            if let Some(key) = binding.key {
+                encoded.push_str(&format!(" key:{:?}", key));
+            }
+
+            encoded.push('\n');
+        }
+
+        encoded
+    }
+
+    /// Actions missing from the text keep their default bindings.
+    pub fn decode(encoded: &str) -> Result<InputMap, InputMapDecodeError> {
+        let mut input_map = InputMap::default();
+
+        for line in encoded
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let invalid_line = || InputMapDecodeError::InvalidLine(line.to_string());
+
+            let (id, bindings) = line.split_once('=').ok_or_else(invalid_line)?;
+            let action = Action::list()
+                .iter()
+                .copied()
+                .find(|action| action.id() == id.trim())
+                .ok_or_else(invalid_line)?;
+
+            let mut binding = ActionBinding::default();
+
+            for token in bindings.split_whitespace() {
+                match token.split_once(':') {
+                    Some(("mouse", name)) => {
+                        binding.mouse = Some(
+                            BINDABLE_BUTTONS
+                                .iter()
+                                .copied()
+                                .find(|button| format!("{:?}", button) == name)
+                                .ok_or_else(invalid_line)?,
+                        );
+                    }
+
+                    Some(("key", name)) => {
+                        binding.key = Some(
+                            BINDABLE_KEYS
+                                .iter()
+                                .copied()
+                                .find(|key| format!("{:?}", key) == name)
+                                .ok_or_else(invalid_line)?,
+                        );
+                    }
+
+                    _ => return Err(invalid_line()),
+                }
+            }
+
+            input_map.bindings.insert(action, binding);
+        }
+
+        Ok(input_map)
+    }
+
+    /// Falls back to the default bindings when there is no usable settings file.
+    pub fn load() -> InputMap {
+        let encoded = match read_text_file(INPUT_MAP_FILE) {
+            Ok(encoded) => encoded,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return InputMap::default(),
+            Err(error) => {
+                warn!("Could not read {}: {}", INPUT_MAP_FILE, error);
+                return InputMap::default();
+            }
+        };
+
+        InputMap::decode(&encoded).unwrap_or_else(|error| {
+            warn!("Could not load input bindings: {:?}", error);
+            InputMap::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Err(error) = write_text_file(INPUT_MAP_FILE, &self.encode()) {
+            warn!("Could not write {}: {}", INPUT_MAP_FILE, error);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum InputMapDecodeError {
+    InvalidLine(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_a_key_takes_it_from_other_actions() {
+        let mut input_map = InputMap::default();
+
+        input_map.bind_key(Action::Undo, KeyCode::R);
+
+        assert_eq!(input_map.binding(Action::Undo).key, Some(KeyCode::R));
+        assert_eq!(input_map.binding(Action::Rotate).key, None);
+    }
+
+    #[test]
+    fn binding_a_button_keeps_it_for_the_shared_gesture() {
+        let mut input_map = InputMap::default();
+
+        input_map.bind_mouse(Action::Pan, MouseButton::Middle);
+
+        assert_eq!(input_map.button(Action::Pan), Some(MouseButton::Middle));
+        assert_eq!(input_map.button(Action::Pick), None);
+        assert_eq!(input_map.button(Action::HoldGhost), None);
+        assert_eq!(input_map.button(Action::Cancel), Some(MouseButton::Right));
+
+        input_map.bind_mouse(Action::Cancel, MouseButton::Middle);
+
+        assert_eq!(input_map.button(Action::Pan), Some(MouseButton::Middle));
+        assert_eq!(input_map.button(Action::Cancel), Some(MouseButton::Middle));
+    }
+}
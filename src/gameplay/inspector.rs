@@ -0,0 +1,240 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ninepatch::{NinePatchBundle, NinePatchData};
+
+use crate::{
+    assets::{Fonts, Images, NinePatches},
+    palette,
+};
+
+use super::{
+    hud::ToolGhost,
+    input::WorldMouseEvent,
+    input_map::{Action, InputMap},
+    machines::PlacedMachine,
+    selection::{is_box_selecting, Selection},
+    tile_tracked_entities::{TilePosition, TileTrackedEntities},
+};
+
+pub fn setup_inspector(
+    mut commands: Commands,
+    images: Res<Images>,
+    fonts: Res<Fonts>,
+    ninepatches: Res<NinePatches>,
+) {
+    commands.insert_resource(Inspector::default());
+
+    let content = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|content| {
+            content
+                .spawn(TextBundle {
+                    text: Text::from_sections([
+                        TextSection::new(
+                            "",
+                            TextStyle {
+                                font: fonts.varela.clone(),
+                                color: palette::DARK_BLUE,
+                                font_size: 32.0,
+                            },
+                        ),
+                        TextSection::new(
+                            "",
+                            TextStyle {
+                                font: fonts.varela.clone(),
+                                color: palette::LIGHT_BROWN,
+                                font_size: 20.0,
+                            },
+                        ),
+                    ]),
+                    focus_policy: FocusPolicy::Pass,
+                    ..default()
+                })
+                .insert(InspectorText);
+        })
+        .insert(Name::new("Inspector Content"))
+        .id();
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(16.0),
+                    top: Val::Px(80.0),
+                    ..default()
+                },
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .with_children(|inspector| {
+            inspector.spawn(NinePatchBundle {
+                nine_patch_data: NinePatchData::with_single_content(
+                    images.panel.clone(),
+                    ninepatches.panel.clone(),
+                    content,
+                ),
+                ..default()
+            });
+        })
+        .insert(Name::new("Inspector"))
+        .insert(InspectorPanel);
+}
+
+/// Clicking a machine with no tool selected opens it in the inspector,
+/// clicking anywhere else closes the inspector.
+pub fn inspect_machine_on_click(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut world_mouse_events: EventReader<WorldMouseEvent>,
+    mut inspector: ResMut<Inspector>,
+    tool_ghosts: Query<(), With<ToolGhost>>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    machines: Query<(), With<PlacedMachine>>,
+) {
+    if !tool_ghosts.is_empty() || is_box_selecting(&keys) {
+        world_mouse_events.clear();
+        return;
+    }
+
+    for event in world_mouse_events.iter() {
+        if let WorldMouseEvent::Click { button, position } = event {
+            if !input_map.is_button(Action::Place, *button) {
+                continue;
+            }
+
+            let tile_position = TilePosition::from_world(*position);
+
+            inspector.machine = tile_tracked_entities
+                .get_entities_in_tile(tile_position)
+                .and_then(|entities| {
+                    entities
+                        .iter()
+                        .copied()
+                        .find(|entity| machines.contains(*entity))
+                });
+        }
+    }
+}
+
+pub fn close_inspector(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut inspector: ResMut<Inspector>,
+) {
+    if input_map.just_pressed(Action::Cancel, &keys) {
+        inspector.machine = None;
+    }
+}
+
+/// While there is a selection the toggle action belongs to the selection actions.
+pub fn toggle_inspected_machine(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    inspector: Res<Inspector>,
+    selection: Res<Selection>,
+    mut machines: Query<&mut PlacedMachine>,
+) {
+    if selection.area.is_some() || !input_map.just_pressed(Action::Toggle, &keys) {
+        return;
+    }
+
+    if let Some(mut placed_machine) = inspector
+        .machine
+        .and_then(|entity| machines.get_mut(entity).ok())
+    {
+        placed_machine.enabled = !placed_machine.enabled;
+    }
+}
+
+pub fn update_inspector(
+    mut inspector: ResMut<Inspector>,
+    input_map: Res<InputMap>,
+    machines: Query<(&Transform, &PlacedMachine)>,
+    mut panels: Query<&mut Visibility, With<InspectorPanel>>,
+    mut texts: Query<&mut Text, With<InspectorText>>,
+) {
+    let inspected = inspector
+        .machine
+        .and_then(|entity| machines.get(entity).ok());
+
+    // The machine may have been deleted while it was inspected
+    if inspected.is_none() && inspector.machine.is_some() {
+        inspector.machine = None;
+    }
+
+    for mut visibility in panels.iter_mut() {
+        if visibility.is_visible != inspected.is_some() {
+            visibility.is_visible = inspected.is_some();
+        }
+    }
+
+    let (transform, placed_machine) = if let Some(inspected) = inspected {
+        inspected
+    } else {
+        return;
+    };
+
+    let machine = placed_machine.machine;
+    let position = TilePosition::from_world(transform.translation.truncate());
+    let stats = placed_machine.stats;
+    let state = if placed_machine.enabled {
+        "Running"
+    } else {
+        "Disabled"
+    };
+
+    let name = machine.name();
+    let details = format!(
+        "\n{} ({}: toggle)\
+         \nPosition: {}, {}\
+         \nActs every {:.1}s\
+         \nNext action in {:.1}s\
+         \nCoins consumed: {}\
+         \nCoins produced: {}\
+         \nValue output: {}\
+         \nIdle: {}%",
+        state,
+        input_map.binding(Action::Toggle).describe(),
+        position.x,
+        position.y,
+        machine.action_period().as_secs_f32(),
+        placed_machine.action_timer.remaining_secs(),
+        stats.coins_consumed,
+        stats.coins_produced,
+        stats.value_output,
+        stats.idle_percent(),
+    );
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != name {
+            text.sections[0].value = name.to_string();
+        }
+
+        if text.sections[1].value != details {
+            text.sections[1].value = details.clone();
+        }
+    }
+}
+
+/// The placed machine shown in the inspector panel.
+#[derive(Resource, Default)]
+pub struct Inspector {
+    pub machine: Option<Entity>,
+}
+
+#[derive(Component)]
+pub struct InspectorPanel;
+
+#[derive(Component)]
+pub struct InspectorText;
@@ -1,4 +1,5 @@
 use bevy::{math::vec3, prelude::*};
+use rand::Rng;
 
 use std::{f32::consts::PI, time::Duration};
 
@@ -8,7 +9,9 @@ use crate::{
 };
 
 use super::{
-    components::{Balance, Coin, CoinPickup, Currency, Money, NextCoinDepth},
+    components::{Balance, Coin, CoinPickup, Currency, GameRng, Money, NextCoinDepth},
+    history::{Edit, EditHistory},
+    settings::Settings,
     systems::spawn_coin,
     tile_tracked_entities::{TilePosition, TileTrackedEntities, TileTrackedEntity},
     HALF_TILE_SIZE,
@@ -19,6 +22,7 @@ pub fn act_machines(
     fonts: Res<Fonts>,
     images: Res<Images>,
     mut depth: ResMut<NextCoinDepth>,
+    mut rng: ResMut<GameRng>,
     mut machines: Query<(&Transform, &mut PlacedMachine)>,
     mut coins: Query<(&mut Coin, &Money), Without<PlacedMachine>>,
     tile_tracked_entities: Res<TileTrackedEntities>,
@@ -28,9 +32,17 @@ pub fn act_machines(
     let mut consumed_coins = Vec::new();
     for (transform, mut placed_machine) in machines.iter_mut() {
         consumed_coins.clear();
+
+        if !placed_machine.enabled {
+            continue;
+        }
+
         placed_machine.action_timer.tick(time.delta());
 
         if placed_machine.action_timer.just_finished() {
+            let mut produced_values = Vec::new();
+            let mut collected_value = 0;
+
             let find_coin = |tile_pos: TilePosition| -> Option<(Entity, &Coin, &Money)> {
                 if let Some(entities) = tile_tracked_entities.get_entities_in_tile(tile_pos) {
                     for &entity in entities {
@@ -48,10 +60,9 @@ pub fn act_machines(
             };
 
             let mut spew_coin = |position: Vec2, value: Currency, angle: f32| {
-                let spread = PI / 4.0;
-                let speed = 80.0 + 30.0 * rand::random::<f32>();
-                let velocity =
-                    Vec2::from_angle(rand::random::<f32>() * spread - spread / 2.0 + angle) * speed;
+                produced_values.push(value);
+
+                let velocity = spew_velocity(&mut rng, angle);
                 spawn_coin(
                     &mut commands,
                     &mut depth,
@@ -73,8 +84,9 @@ pub fn act_machines(
                 }
 
                 Machine::Collector => {
-                    if let Some((entity, _coin, _)) = find_coin(tile_pos.offset(0, 1)) {
+                    if let Some((entity, _coin, money)) = find_coin(tile_pos.offset(0, 1)) {
                         consumed_coins.push(entity);
+                        collected_value += money.0;
                         coin_pickups.send(CoinPickup {
                             coin: entity,
                             target: position,
@@ -201,6 +213,15 @@ pub fn act_machines(
                     }
                 }
             }
+
+            let stats = &mut placed_machine.stats;
+            stats.actions += 1;
+            if consumed_coins.is_empty() && produced_values.is_empty() {
+                stats.idle_actions += 1;
+            }
+            stats.coins_consumed += consumed_coins.len() as u32;
+            stats.coins_produced += produced_values.len() as u32;
+            stats.value_output += produced_values.iter().sum::<Currency>() + collected_value;
         }
 
         for coin_entity in consumed_coins.iter() {
@@ -210,73 +231,212 @@ pub fn act_machines(
     }
 }
 
+/// Coins produced by machines fly out in roughly the direction of the angle.
+pub fn spew_velocity(rng: &mut GameRng, angle: f32) -> Vec2 {
+    let spread = PI / 4.0;
+    let speed = 80.0 + 30.0 * rng.gen::<f32>();
+
+    Vec2::from_angle(rng.gen::<f32>() * spread - spread / 2.0 + angle) * speed
+}
+
 pub fn place_machines(
     mut commands: Commands,
     mut requests: EventReader<MachinePlaceRequest>,
+    mut delete_requests: EventReader<MachineDeleteRequest>,
     mut balance: ResMut<Balance>,
     tile_tracked_entities: Res<TileTrackedEntities>,
     machines: Query<&PlacedMachine>,
     images: Res<Images>,
+    mut history: ResMut<EditHistory>,
     mut update_spots_requests: EventWriter<UpdateSpotsRequest>,
+    mut rejections: EventWriter<MachinePlaceRejected>,
 ) {
-    'requests: for request in requests.iter() {
+    // Undoing a rotation deletes and places a machine on the same tile in the same frame
+    let history_deletes: Vec<_> = delete_requests
+        .iter()
+        .filter(|request| request.history_refund.is_some())
+        .map(|request| request.position)
+        .collect();
+
+    // Tile tracking only catches up next frame, so two requests for one tile would both pass
+    let mut placed_positions = Vec::new();
+
+    for request in requests.iter() {
         let machine = request.machine;
-        let machine_cost = machine.cost();
 
-        if machine_cost > balance.coins {
-            continue 'requests;
+        if placed_positions.contains(&request.position) {
+            rejections.send(MachinePlaceRejected {
+                position: request.position,
+                reason: PlacementError::Occupied,
+            });
+            continue;
         }
 
-        if let Some(entities) = tile_tracked_entities.get_entities_in_tile(request.position) {
-            for tile_entity in entities {
-                if machines.get(*tile_entity).is_ok() {
-                    continue 'requests;
-                }
-            }
+        let machine_cost = request.history_cost.unwrap_or_else(|| machine.cost());
+
+        let freed = request.history_cost.is_some() && history_deletes.contains(&request.position);
+
+        let placement = if freed && machine_cost <= balance.coins {
+            Ok(())
+        } else {
+            check_placement(
+                machine_cost,
+                request.position,
+                balance.coins,
+                &tile_tracked_entities,
+                &machines,
+            )
+        };
+
+        if let Err(reason) = placement {
+            rejections.send(MachinePlaceRejected {
+                position: request.position,
+                reason,
+            });
+            continue;
         }
 
         balance.coins -= machine_cost;
-        let placed_machine = machine.spawn_graphics(&mut commands, &images, true);
-        commands
-            .entity(placed_machine)
-            .insert(Transform::from_translation(
-                (request.position.to_world() + Vec2::splat(HALF_TILE_SIZE)).extend(0.0),
-            ))
-            .insert(PlacedMachine {
+        let placed_machine = PlacedMachine {
+            enabled: request.enabled,
+            ..PlacedMachine::new(machine)
+        };
+
+        spawn_placed_machine(&mut commands, &images, placed_machine, request.position);
+        placed_positions.push(request.position);
+
+        if request.history_cost.is_none() {
+            history.record(Edit::Place {
                 machine,
-                action_timer: Timer::new(machine.action_period(), TimerMode::Repeating),
-            })
-            .insert(TileTrackedEntity);
-
-        let update_positions = vec![
-            request.position,
-            request.position.offset(-1, 0),
-            request.position.offset(1, 0),
-            request.position.offset(0, -1),
-            request.position.offset(0, 1),
-        ];
-
-        for position in update_positions {
-            update_spots_requests.send(UpdateSpotsRequest { position });
+                position: request.position,
+                cost: machine_cost,
+                enabled: request.enabled,
+            });
+        }
+
+        request_spot_updates(request.position, &mut update_spots_requests);
+    }
+}
+
+/// Checks whether the player could place a machine for `cost` on the tile right now.
+pub fn check_placement(
+    cost: Currency,
+    position: TilePosition,
+    coins: Currency,
+    tile_tracked_entities: &TileTrackedEntities,
+    machines: &Query<&PlacedMachine>,
+) -> Result<(), PlacementError> {
+    if let Some(entities) = tile_tracked_entities.get_entities_in_tile(position) {
+        if entities.iter().any(|entity| machines.contains(*entity)) {
+            return Err(PlacementError::Occupied);
         }
     }
+
+    if cost > coins {
+        return Err(PlacementError::InsufficientCoins);
+    }
+
+    Ok(())
+}
+
+pub fn spawn_placed_machine(
+    commands: &mut Commands,
+    images: &Images,
+    placed_machine: PlacedMachine,
+    position: TilePosition,
+) -> Entity {
+    let entity = placed_machine
+        .machine
+        .spawn_graphics(commands, images, true);
+
+    commands
+        .entity(entity)
+        .insert(Transform::from_translation(
+            (position.to_world() + Vec2::splat(HALF_TILE_SIZE)).extend(0.0),
+        ))
+        .insert(placed_machine)
+        .insert(TileTrackedEntity);
+
+    entity
+}
+
+/// Spots of a machine placed at the position may overlap machines on the neighbouring tiles.
+pub fn request_spot_updates(
+    position: TilePosition,
+    update_spots_requests: &mut EventWriter<UpdateSpotsRequest>,
+) {
+    let update_positions = [
+        position,
+        position.offset(-1, 0),
+        position.offset(1, 0),
+        position.offset(0, -1),
+        position.offset(0, 1),
+    ];
+
+    for position in update_positions {
+        update_spots_requests.send(UpdateSpotsRequest { position });
+    }
 }
 
 pub fn delete_machines(
     mut commands: Commands,
     mut requests: EventReader<MachineDeleteRequest>,
+    mut balance: ResMut<Balance>,
+    settings: Res<Settings>,
+    mut confirmation: ResMut<DeleteConfirmation>,
     tile_tracked_entities: Res<TileTrackedEntities>,
     machines: Query<&PlacedMachine>,
+    time: Res<Time>,
+    mut history: ResMut<EditHistory>,
     mut update_spots_requests: EventWriter<UpdateSpotsRequest>,
 ) {
+    confirmation.timer.tick(time.delta());
+
+    if confirmation.timer.just_finished() {
+        confirmation.positions.clear();
+    }
+
+    // A drag sends all of its requests in one frame, each gesture confirms the previous one
+    let mut gesture = false;
+    let mut pending_positions = Vec::new();
+
     for request in requests.iter() {
+        gesture |= !request.confirmed;
+
         let mut did_delete = false;
 
         if let Some(entities) = tile_tracked_entities.get_entities_in_tile(request.position) {
             for tile_entity in entities {
-                if machines.get(*tile_entity).is_ok() {
+                if let Ok(placed_machine) = machines.get(*tile_entity) {
+                    let machine = placed_machine.machine;
+
+                    if let Some(refund) = request.history_refund {
+                        commands.entity(*tile_entity).despawn_recursive();
+                        balance.coins += refund;
+                        did_delete = true;
+                        continue;
+                    }
+
+                    if machine.cost() >= settings.confirm_delete_cost
+                        && !request.confirmed
+                        && !confirmation.positions.contains(&request.position)
+                    {
+                        pending_positions.push(request.position);
+                        continue;
+                    }
+
+                    let refund = settings.refund(machine);
+
                     commands.entity(*tile_entity).despawn_recursive();
+                    balance.coins += refund;
                     did_delete = true;
+
+                    history.record(Edit::Delete {
+                        machine,
+                        position: request.position,
+                        refund,
+                        enabled: placed_machine.enabled,
+                    });
                 }
             }
         }
@@ -287,6 +447,14 @@ pub fn delete_machines(
             });
         }
     }
+
+    if gesture {
+        if !pending_positions.is_empty() {
+            confirmation.timer.reset();
+        }
+
+        confirmation.positions = pending_positions;
+    }
 }
 
 pub fn update_spots(
@@ -314,7 +482,7 @@ pub fn update_spots(
     }
 }
 
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Machine {
     Miner,
     Collector,
@@ -387,6 +555,68 @@ impl Machine {
         }
     }
 
+    pub fn is_conveyor(&self) -> bool {
+        use Machine::*;
+
+        matches!(
+            self,
+            ConveyorUp | ConveyorDown | ConveyorLeft | ConveyorRight
+        )
+    }
+
+    /// Conveyor moving coins along the given tile offset.
+    pub fn conveyor_towards(dx: i32, dy: i32) -> Machine {
+        use Machine::*;
+
+        if dx.abs() >= dy.abs() {
+            if dx >= 0 {
+                ConveyorRight
+            } else {
+                ConveyorLeft
+            }
+        } else if dy >= 0 {
+            ConveyorUp
+        } else {
+            ConveyorDown
+        }
+    }
+
+    /// The same machine turned clockwise. Only conveyors have a direction.
+    pub fn rotated(&self) -> Machine {
+        use Machine::*;
+
+        match self {
+            ConveyorUp => ConveyorRight,
+            ConveyorRight => ConveyorDown,
+            ConveyorDown => ConveyorLeft,
+            ConveyorLeft => ConveyorUp,
+            other => *other,
+        }
+    }
+
+    /// Single-character code used in the text form of blueprints.
+    pub fn code(&self) -> char {
+        use Machine::*;
+
+        match self {
+            Miner => 'M',
+            Collector => 'C',
+            ConveyorUp => 'U',
+            ConveyorDown => 'D',
+            ConveyorLeft => 'L',
+            ConveyorRight => 'R',
+            Adder => 'A',
+            Multiplier => 'X',
+        }
+    }
+
+    pub fn from_code(code: char) -> Option<Machine> {
+        Machine::list()
+            .iter()
+            .copied()
+            .find(|machine| machine.code() == code)
+    }
+
     pub fn action_period(&self) -> Duration {
         use Machine::*;
 
@@ -483,15 +713,80 @@ impl Machine {
 pub struct PlacedMachine {
     pub machine: Machine,
     pub action_timer: Timer,
+    /// Disabled machines keep their place but stop acting.
+    pub enabled: bool,
+    pub stats: MachineStats,
+}
+
+impl PlacedMachine {
+    pub fn new(machine: Machine) -> PlacedMachine {
+        PlacedMachine {
+            machine,
+            action_timer: Timer::new(machine.action_period(), TimerMode::Repeating),
+            enabled: true,
+            stats: MachineStats::default(),
+        }
+    }
+}
+
+/// Running totals of a placed machine, shown in the inspector.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MachineStats {
+    pub actions: u32,
+    /// Actions where the machine had no coins to work with.
+    pub idle_actions: u32,
+    pub coins_consumed: u32,
+    pub coins_produced: u32,
+    /// Value of the produced coins, or of the collected coins for collectors.
+    pub value_output: Currency,
+}
+
+impl MachineStats {
+    pub fn idle_percent(&self) -> u32 {
+        (self.idle_actions * 100)
+            .checked_div(self.actions)
+            .unwrap_or(0)
+    }
 }
 
 pub struct MachinePlaceRequest {
     pub machine: Machine,
     pub position: TilePosition,
+    /// Set by undo and redo, which charge this instead of the machine cost
+    /// and do not record the edit again.
+    pub history_cost: Option<Currency>,
+    /// Undo and redo bring back machines that were turned off as they were.
+    pub enabled: bool,
+}
+
+/// Sent when a placement requested by the player could not be made.
+pub struct MachinePlaceRejected {
+    pub position: TilePosition,
+    pub reason: PlacementError,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlacementError {
+    Occupied,
+    InsufficientCoins,
+}
+
+impl PlacementError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            PlacementError::Occupied => "Tile is occupied",
+            PlacementError::InsufficientCoins => "Not enough coins",
+        }
+    }
 }
 
 pub struct MachineDeleteRequest {
     pub position: TilePosition,
+    /// Set by undo and redo, which refund this instead of the usual share of the cost
+    /// and do not record the edit again.
+    pub history_refund: Option<Currency>,
+    /// Skips the confirmation otherwise required for expensive machines.
+    pub confirmed: bool,
 }
 
 pub struct UpdateSpotsRequest {
@@ -500,3 +795,19 @@ pub struct UpdateSpotsRequest {
 
 #[derive(Component)]
 pub struct Spot;
+
+/// Expensive machines the last delete gesture went over, deleted if the next gesture does too.
+#[derive(Resource)]
+pub struct DeleteConfirmation {
+    pub positions: Vec<TilePosition>,
+    pub timer: Timer,
+}
+
+impl Default for DeleteConfirmation {
+    fn default() -> Self {
+        DeleteConfirmation {
+            positions: Vec::new(),
+            timer: Timer::from_seconds(2.0, TimerMode::Once),
+        }
+    }
+}
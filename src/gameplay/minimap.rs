@@ -0,0 +1,326 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    ui::FocusPolicy,
+};
+use bevy_ninepatch::{NinePatchBundle, NinePatchData};
+
+use crate::{
+    assets::{Images, NinePatches},
+    palette,
+};
+
+use super::{
+    components::Coin,
+    machines::{Machine, PlacedMachine},
+    navigation::CameraFlight,
+    TILE_SIZE,
+};
+
+/// Width and height of the minimap, in pixels of both the image and the window.
+const MINIMAP_SIZE: u32 = 160;
+/// Seconds between redraws, drawing every frame would upload the image every frame.
+const MINIMAP_REDRAW_PERIOD: f32 = 0.1;
+/// Tiles shown around the machines, coins and camera view.
+const MINIMAP_MARGIN: f32 = 4.0;
+/// Fewest tiles across the minimap, so a small factory is not blown up.
+const MINIMAP_MIN_TILES: f32 = 24.0;
+/// Coins on a pixel for it to be drawn at full strength.
+const FULL_COIN_DENSITY: f32 = 4.0;
+
+pub fn setup_minimap(
+    mut commands: Commands,
+    images: Res<Images>,
+    ninepatches: Res<NinePatches>,
+    mut image_assets: ResMut<Assets<Image>>,
+) {
+    let image = image_assets.add(Image::new_fill(
+        Extent3d {
+            width: MINIMAP_SIZE,
+            height: MINIMAP_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &color_bytes(palette::OFF_WHITE),
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    let content = commands
+        .spawn(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(MINIMAP_SIZE as f32), Val::Px(MINIMAP_SIZE as f32)),
+                margin: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            image: image.clone().into(),
+            ..default()
+        })
+        .insert(Name::new("Minimap Image"))
+        .insert(MinimapImage)
+        .id();
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(16.0),
+                    bottom: Val::Px(16.0),
+                    ..default()
+                },
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|minimap| {
+            minimap.spawn(NinePatchBundle {
+                nine_patch_data: NinePatchData::with_single_content(
+                    images.panel.clone(),
+                    ninepatches.panel.clone(),
+                    content,
+                ),
+                ..default()
+            });
+        })
+        .insert(Name::new("Minimap"));
+
+    commands.insert_resource(Minimap {
+        image,
+        bounds: Rect::new(0.0, 0.0, 0.0, 0.0),
+        redraw_timer: Timer::from_seconds(MINIMAP_REDRAW_PERIOD, TimerMode::Repeating),
+    });
+}
+
+/// Draws loose coins as a heat map, machines colored by kind on top of them,
+/// and the outline of what the camera sees.
+pub fn draw_minimap(
+    mut minimap: ResMut<Minimap>,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    mut image_assets: ResMut<Assets<Image>>,
+    machines: Query<(&Transform, &PlacedMachine)>,
+    coins: Query<(&Transform, &Coin)>,
+    camera: Query<&Transform, With<Camera2d>>,
+) {
+    minimap.redraw_timer.tick(time.delta());
+
+    if !minimap.redraw_timer.just_finished() {
+        return;
+    }
+
+    let window = if let Some(window) = windows.get_primary() {
+        window
+    } else {
+        return;
+    };
+
+    let camera_transform = camera.single();
+    let half_view = Vec2::new(window.width(), window.height()) * camera_transform.scale.x / 2.0;
+    let view_center = camera_transform.translation.truncate();
+
+    let machine_positions = machines
+        .iter()
+        .map(|(transform, _)| transform.translation.truncate());
+    let coin_positions = coins
+        .iter()
+        .filter(|(_, coin)| coin.alive)
+        .map(|(transform, _)| transform.translation.truncate());
+    let view_corners = [view_center - half_view, view_center + half_view];
+
+    minimap.bounds = minimap_bounds(machine_positions.chain(coin_positions).chain(view_corners));
+
+    let image = if let Some(image) = image_assets.get_mut(&minimap.image) {
+        image
+    } else {
+        return;
+    };
+
+    let bounds = minimap.bounds;
+    let pixels_per_unit = MINIMAP_SIZE as f32 / bounds.width();
+    let size = MINIMAP_SIZE as usize;
+
+    // Pixel rows start at the top, the world y axis points up
+    let to_pixel = |position: Vec2| -> Vec2 {
+        let pixel = (position - bounds.min) * pixels_per_unit;
+        Vec2::new(pixel.x, MINIMAP_SIZE as f32 - pixel.y)
+    };
+
+    let mut pixels = vec![palette::OFF_WHITE; size * size];
+
+    let mut coin_counts = vec![0.0f32; size * size];
+
+    for (transform, coin) in coins.iter() {
+        if !coin.alive {
+            continue;
+        }
+
+        let pixel = to_pixel(transform.translation.truncate());
+
+        if let Some(index) = pixel_index(pixel.x, pixel.y) {
+            coin_counts[index] += 1.0;
+        }
+    }
+
+    for (pixel, count) in pixels.iter_mut().zip(coin_counts) {
+        if count > 0.0 {
+            *pixel = mix(
+                *pixel,
+                palette::ORANGE,
+                (count / FULL_COIN_DENSITY).min(1.0),
+            );
+        }
+    }
+
+    let machine_pixels = (TILE_SIZE * pixels_per_unit).max(1.0);
+
+    for (transform, placed_machine) in machines.iter() {
+        let corner = to_pixel(transform.translation.truncate()) - Vec2::splat(machine_pixels / 2.0);
+        let color = machine_color(placed_machine.machine);
+
+        fill_rect(
+            &mut pixels,
+            corner,
+            corner + Vec2::splat(machine_pixels),
+            color,
+        );
+    }
+
+    // World y grows upwards, so the top left of the view is at its highest y
+    let view_min = to_pixel(view_center + Vec2::new(-half_view.x, half_view.y));
+    let view_max = to_pixel(view_center + Vec2::new(half_view.x, -half_view.y));
+
+    outline_rect(&mut pixels, view_min, view_max, palette::DARK_BLUE);
+
+    image.data = pixels.into_iter().flat_map(color_bytes).collect();
+}
+
+/// Pressing or holding the button on the minimap centers the camera on that point.
+pub fn move_camera_on_minimap(
+    minimap: Res<Minimap>,
+    windows: Res<Windows>,
+    minimap_images: Query<(&Interaction, &Node, &GlobalTransform), With<MinimapImage>>,
+    mut flight: ResMut<CameraFlight>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    let window = if let Some(window) = windows.get_primary() {
+        window
+    } else {
+        return;
+    };
+
+    // UI nodes are laid out from the top left corner of the window
+    let cursor_position = if let Some(position) = window.cursor_position() {
+        Vec2::new(position.x, window.height() - position.y)
+    } else {
+        return;
+    };
+
+    for (interaction, node, global_transform) in minimap_images.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let node_min = global_transform.translation().truncate() - node.size() / 2.0;
+        let fraction = ((cursor_position - node_min) / node.size()).clamp(Vec2::ZERO, Vec2::ONE);
+        let bounds = minimap.bounds;
+        let position = bounds.min + Vec2::new(fraction.x, 1.0 - fraction.y) * bounds.size();
+
+        flight.stop();
+
+        let mut camera_transform = camera.single_mut();
+        camera_transform.translation = position.extend(camera_transform.translation.z);
+    }
+}
+
+/// Square area around the machines, loose coins and camera view shown on the minimap.
+fn minimap_bounds(positions: impl Iterator<Item = Vec2>) -> Rect {
+    let mut positions = positions.peekable();
+
+    let (min, max) = if positions.peek().is_some() {
+        positions.fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), position| (min.min(position), max.max(position)),
+        )
+    } else {
+        (Vec2::ZERO, Vec2::ZERO)
+    };
+
+    let center = (min + max) / 2.0;
+    let span = ((max - min).max_element() + 2.0 * MINIMAP_MARGIN * TILE_SIZE)
+        .max(MINIMAP_MIN_TILES * TILE_SIZE);
+
+    Rect::from_center_size(center, Vec2::splat(span))
+}
+
+fn machine_color(machine: Machine) -> Color {
+    use Machine::*;
+
+    match machine {
+        Miner => palette::LIGHT_BROWN,
+        Collector => palette::BLUE,
+        ConveyorUp | ConveyorDown | ConveyorLeft | ConveyorRight => palette::LIGHT_BLUE,
+        Adder => palette::GREEN,
+        Multiplier => palette::PURPLE,
+    }
+}
+
+fn pixel_index(x: f32, y: f32) -> Option<usize> {
+    let size = MINIMAP_SIZE as f32;
+
+    if (0.0..size).contains(&x) && (0.0..size).contains(&y) {
+        Some(y as usize * MINIMAP_SIZE as usize + x as usize)
+    } else {
+        None
+    }
+}
+
+/// Fills the pixels from `min` up to `max`, leaving out what is off the image.
+fn fill_rect(pixels: &mut [Color], min: Vec2, max: Vec2, color: Color) {
+    let size = MINIMAP_SIZE as f32;
+    let min = min.round().clamp(Vec2::ZERO, Vec2::splat(size));
+    let max = max.round().clamp(Vec2::ZERO, Vec2::splat(size));
+
+    for y in min.y as usize..max.y as usize {
+        for x in min.x as usize..max.x as usize {
+            pixels[y * MINIMAP_SIZE as usize + x] = color;
+        }
+    }
+}
+
+fn outline_rect(pixels: &mut [Color], min: Vec2, max: Vec2, color: Color) {
+    fill_rect(pixels, min, Vec2::new(max.x, min.y + 1.0), color);
+    fill_rect(pixels, Vec2::new(min.x, max.y - 1.0), max, color);
+    fill_rect(pixels, min, Vec2::new(min.x + 1.0, max.y), color);
+    fill_rect(pixels, Vec2::new(max.x - 1.0, min.y), max, color);
+}
+
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let [r, g, b, a] = from.as_rgba_f32();
+    let [to_r, to_g, to_b, to_a] = to.as_rgba_f32();
+
+    Color::rgba(
+        r + (to_r - r) * amount,
+        g + (to_g - g) * amount,
+        b + (to_b - b) * amount,
+        a + (to_a - a) * amount,
+    )
+}
+
+/// Bytes of the color in an `Rgba8UnormSrgb` image.
+pub fn color_bytes(color: Color) -> [u8; 4] {
+    color
+        .as_rgba_f32()
+        .map(|component| (component.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+#[derive(Resource)]
+pub struct Minimap {
+    image: Handle<Image>,
+    /// World area drawn on the minimap when it was last redrawn.
+    bounds: Rect,
+    redraw_timer: Timer,
+}
+
+#[derive(Component)]
+pub struct MinimapImage;
@@ -1,20 +1,36 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::AsSystemLabel, input::InputSystem, prelude::*, ui::UiSystem};
 use iyes_loopless::prelude::*;
 
-use crate::{can_use_mouse, GameState, GameSystemLabel};
+use crate::{can_use_mouse, should_use_keyboard, GameState, GameSystemLabel};
 
 use self::{
     components::*,
     hud::ToolbarButtonSelectedEvent,
-    machines::{MachineDeleteRequest, MachinePlaceRequest, UpdateSpotsRequest},
+    machines::{
+        MachineDeleteRequest, MachinePlaceRejected, MachinePlaceRequest, UpdateSpotsRequest,
+    },
 };
 
 pub mod components;
 pub mod systems;
 
+pub mod balance_graph;
+pub mod blueprints;
+pub mod gamepad;
+pub mod grid;
+pub mod history;
+pub mod hotkeys;
 pub mod hud;
 pub mod input;
+pub mod input_map;
+pub mod inspector;
 pub mod machines;
+pub mod minimap;
+pub mod navigation;
+pub mod rebinding;
+pub mod replay;
+pub mod selection;
+pub mod settings;
 pub mod tile_tracked_entities;
 
 pub const TILE_SIZE: f32 = 64.0 * 4.0;
@@ -25,26 +41,118 @@ pub struct GameplayPlugin;
 impl Plugin for GameplayPlugin {
     fn build(&self, app: &mut App) {
         app.add_enter_system(GameState::Gameplay, systems::startup_gameplay)
-            .add_enter_system(GameState::Gameplay, hud::setup_hud);
+            .add_enter_system(GameState::Gameplay, hud::setup_hud)
+            .add_enter_system(GameState::Gameplay, selection::setup_selection)
+            .add_enter_system(GameState::Gameplay, inspector::setup_inspector)
+            .add_enter_system(GameState::Gameplay, grid::setup_grid)
+            .add_enter_system(GameState::Gameplay, navigation::setup_navigation)
+            .add_enter_system(GameState::Gameplay, minimap::setup_minimap)
+            .add_enter_system(GameState::Gameplay, balance_graph::setup_balance_graph)
+            .add_enter_system(GameState::Gameplay, gamepad::setup_virtual_cursor)
+            .add_enter_system(GameState::Gameplay, rebinding::setup_rebinding_screen)
+            .add_enter_system(
+                GameState::Gameplay,
+                replay::setup_replay.after(systems::startup_gameplay),
+            );
+
+        app.insert_resource(input_map::InputMap::load())
+            .insert_resource(settings::Settings::load())
+            .insert_resource(navigation::CameraBookmarks::load())
+            .init_resource::<replay::Replay>()
+            .init_resource::<GameRng>();
 
         app.add_event::<input::WorldMouseEvent>()
             .add_event::<ToolbarButtonSelectedEvent>()
             .add_event::<MachinePlaceRequest>()
+            .add_event::<MachinePlaceRejected>()
             .add_event::<MachineDeleteRequest>()
             .add_event::<UpdateSpotsRequest>()
-            .add_event::<CoinPickup>();
+            .add_event::<CoinPickup>()
+            .add_event::<settings::SettingToggledEvent>();
+
+        app.add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            ConditionSet::new()
+                .after(InputSystem)
+                .before(UiSystem::Focus)
+                .run_in_state(GameState::Gameplay)
+                .with_system(replay::replay_inputs)
+                .into(),
+        );
+
+        app.add_system_set(
+            ConditionSet::new()
+                .before(GameSystemLabel::InputHandling)
+                .run_if(should_use_keyboard)
+                .run_in_state(GameState::Gameplay)
+                .with_system(rebinding::rebind_actions)
+                .into(),
+        );
+
+        app.add_system_set(
+            ConditionSet::new()
+                .before(GameSystemLabel::InputHandling)
+                .run_in_state(GameState::Gameplay)
+                .with_system(input::handle_touch_input)
+                .with_system(gamepad::update_virtual_cursor)
+                .with_system(gamepad::cycle_tool_on_shoulders)
+                .with_system(gamepad::move_camera_with_gamepad)
+                .into(),
+        );
 
         app.add_system_set(
             ConditionSet::new()
                 .label(GameSystemLabel::InputHandling)
                 .before(GameSystemLabel::PreUpdate)
                 .run_if(can_use_mouse)
+                .run_if_not(replay::replaying)
                 .run_in_state(GameState::Gameplay)
                 .with_system(input::handle_bg_input)
+                .into(),
+        );
+
+        app.add_system_set(
+            ConditionSet::new()
+                .label(GameSystemLabel::InputHandling)
+                .before(GameSystemLabel::PreUpdate)
+                .run_if(can_use_mouse)
+                .run_in_state(GameState::Gameplay)
                 .with_system(input::zoom_camera)
-                .with_system(hud::hide_building_ghost_on_right_click)
+                .with_system(input::scroll_camera_at_edges)
+                .with_system(hud::hide_building_ghost_on_cancel)
                 .with_system(hud::ghost_place_machine)
                 .with_system(hud::ghost_delete_machine)
+                .with_system(hud::ghost_toggle_machine)
+                .with_system(blueprints::ghost_select_blueprint)
+                .with_system(blueprints::ghost_paste_blueprint)
+                .with_system(selection::box_select_machines)
+                .with_system(hud::pick_machine_on_click)
+                .with_system(inspector::inspect_machine_on_click)
+                .into(),
+        );
+
+        app.add_system_set(
+            ConditionSet::new()
+                .label(GameSystemLabel::InputHandling)
+                .before(GameSystemLabel::PreUpdate)
+                .run_if(should_use_keyboard)
+                .run_in_state(GameState::Gameplay)
+                .with_system(systems::toggle_coin_merging)
+                .with_system(blueprints::export_blueprint)
+                .with_system(blueprints::import_blueprint)
+                .with_system(history::undo_redo)
+                .with_system(selection::selection_actions)
+                .with_system(hud::pick_machine_on_key)
+                .with_system(inspector::close_inspector)
+                .with_system(inspector::toggle_inspected_machine)
+                .with_system(grid::toggle_grid)
+                .with_system(hotkeys::select_tool_on_key)
+                .with_system(hotkeys::drop_tool_on_cancel)
+                .with_system(hotkeys::rotate_tool_on_key)
+                .with_system(hotkeys::toggle_pause)
+                .with_system(input::pan_camera_with_keys)
+                .with_system(input::recenter_camera)
+                .with_system(navigation::use_bookmarks)
                 .into(),
         );
 
@@ -64,16 +172,54 @@ impl Plugin for GameplayPlugin {
                 .label(GameSystemLabel::Update)
                 .before(GameSystemLabel::PostUpdate)
                 .with_system(input::drag_camera)
+                .with_system(navigation::jump_to_machine_group)
+                .with_system(navigation::fly_camera)
+                .with_system(minimap::move_camera_on_minimap)
                 .with_system(systems::click_coins)
                 .with_system(systems::hover_coins)
                 .with_system(systems::update_coins)
-                .with_system(systems::move_particles)
+                .with_system(systems::update_coin_label_visibility)
+                .with_system(systems::spawn_stress_scene)
                 .with_system(hud::update_balance_display)
+                .with_system(balance_graph::record_balance_history)
+                .with_system(balance_graph::toggle_balance_graph)
                 .with_system(hud::select_toolbar_button)
                 .with_system(hud::drag_building_ghost)
-                .with_system(machines::act_machines)
-                .with_system(machines::place_machines)
                 .with_system(machines::delete_machines)
+                // Refunds of deletions pay for the placements of the same undo or redo
+                .with_system(
+                    machines::place_machines
+                        .into_conditional()
+                        .after(machines::delete_machines.as_system_label()),
+                )
+                .into(),
+        );
+
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::Gameplay)
+                .run_if(systems::simulation_running)
+                .label(GameSystemLabel::Update)
+                .before(GameSystemLabel::PostUpdate)
+                .with_system(systems::move_particles)
+                .with_system(systems::merge_coins)
+                // Clicks and machines take turns on the game RNG, always in the same order
+                .with_system(
+                    machines::act_machines
+                        .into_conditional()
+                        .after(systems::click_coins.as_system_label())
+                        .after(systems::merge_coins.as_system_label()),
+                )
+                .into(),
+        );
+
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::Gameplay)
+                .after(GameSystemLabel::Update)
+                .before(GameSystemLabel::PostUpdate)
+                .with_system(replay::record_inputs)
+                .with_system(replay::replay_selections)
                 .into(),
         );
 
@@ -83,6 +229,35 @@ impl Plugin for GameplayPlugin {
                 .label(GameSystemLabel::PostUpdate)
                 .with_system(hud::update_selected_machine_button)
                 .with_system(hud::show_hide_building_ghost)
+                .with_system(hud::update_paste_button)
+                .with_system(hud::update_refund_label)
+                .with_system(hud::update_placement_label)
+                .with_system(hud::show_placement_rejections)
+                .with_system(hud::update_drag_preview)
+                .with_system(blueprints::update_selection_ghost)
+                .with_system(blueprints::update_blueprint_ghost)
+                .with_system(history::commit_edit_group)
+                .with_system(selection::update_selection_box)
+                .with_system(selection::tint_machines)
+                .with_system(inspector::update_inspector)
+                .with_system(grid::update_grid)
+                .with_system(grid::update_tile_readout)
+                .with_system(hud::update_pause_label)
+                .with_system(hud::update_merging_label)
+                .with_system(rebinding::update_rebinding_screen)
+                .with_system(gamepad::update_virtual_cursor_image)
+                .with_system(navigation::update_jump_list)
+                .with_system(minimap::draw_minimap)
+                .with_system(hud::update_income_display)
+                .with_system(balance_graph::draw_balance_graph)
+                .into(),
+        );
+
+        app.add_system_set_to_stage(
+            CoreStage::Last,
+            ConditionSet::new()
+                .run_in_state(GameState::Gameplay)
+                .with_system(replay::advance_replay)
                 .into(),
         );
     }
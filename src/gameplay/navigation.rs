@@ -0,0 +1,427 @@
+use std::io;
+
+use bevy::{math::vec3, prelude::*, ui::FocusPolicy};
+use bevy_ninepatch::{NinePatchBundle, NinePatchData};
+
+use crate::{
+    assets::{Fonts, Images, NinePatches},
+    palette,
+};
+
+use super::{
+    input::frame_positions,
+    machines::{Machine, PlacedMachine},
+    settings::{read_text_file, write_text_file, Settings},
+};
+
+const BOOKMARKS_FILE: &str = "bookmarks.cfg";
+
+/// Function keys jump to their bookmark, with Ctrl held they store the current view in it.
+const BOOKMARK_KEYS: [KeyCode; 4] = [KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8];
+
+/// Seconds the camera takes to fly to a bookmark or machine group.
+const FLIGHT_DURATION: f32 = 0.6;
+
+/// Machine groups listed in the jump to panel. Conveyors of all directions form one group.
+const MACHINE_GROUPS: [(&str, &[Machine]); 5] = [
+    ("Miners", &[Machine::Miner]),
+    ("Collectors", &[Machine::Collector]),
+    (
+        "Conveyors",
+        &[
+            Machine::ConveyorUp,
+            Machine::ConveyorDown,
+            Machine::ConveyorLeft,
+            Machine::ConveyorRight,
+        ],
+    ),
+    ("Adders", &[Machine::Adder]),
+    ("Multipliers", &[Machine::Multiplier]),
+];
+
+pub fn setup_navigation(
+    mut commands: Commands,
+    images: Res<Images>,
+    fonts: Res<Fonts>,
+    ninepatches: Res<NinePatches>,
+) {
+    commands.insert_resource(CameraFlight::default());
+
+    let content = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|content| {
+            content.spawn(TextBundle {
+                text: Text::from_section(
+                    "Jump to",
+                    TextStyle {
+                        font: fonts.varela.clone(),
+                        color: palette::DARK_BLUE,
+                        font_size: 24.0,
+                    },
+                ),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            });
+
+            for group in 0..MACHINE_GROUPS.len() {
+                content
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(TextBundle {
+                            text: Text::from_section(
+                                "",
+                                TextStyle {
+                                    font: fonts.varela.clone(),
+                                    color: palette::LIGHT_BROWN,
+                                    font_size: 20.0,
+                                },
+                            ),
+                            focus_policy: FocusPolicy::Pass,
+                            ..default()
+                        })
+                        .insert(JumpLabel(group));
+                    })
+                    .insert(JumpButton(group));
+            }
+        })
+        .insert(Name::new("Jump List Content"))
+        .id();
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(16.0),
+                    top: Val::Px(80.0),
+                    ..default()
+                },
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .with_children(|jump_list| {
+            jump_list.spawn(NinePatchBundle {
+                nine_patch_data: NinePatchData::with_single_content(
+                    images.panel.clone(),
+                    ninepatches.panel.clone(),
+                    content,
+                ),
+                ..default()
+            });
+        })
+        .insert(Name::new("Jump List"))
+        .insert(JumpList);
+}
+
+/// F5 to F8 fly to their bookmark, Ctrl with one of them stores the current view instead.
+pub fn use_bookmarks(
+    keys: Res<Input<KeyCode>>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    settings: Res<Settings>,
+    mut flight: ResMut<CameraFlight>,
+    camera: Query<&Transform, With<Camera2d>>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let camera_transform = camera.single();
+
+    for (slot, key) in BOOKMARK_KEYS.iter().enumerate() {
+        if !keys.just_pressed(*key) {
+            continue;
+        }
+
+        if ctrl {
+            bookmarks.slots[slot] = Some(CameraBookmark {
+                position: camera_transform.translation.truncate(),
+                zoom: camera_transform.scale.x,
+            });
+            bookmarks.save();
+
+            info!("Stored the view in bookmark {}", slot + 1);
+        } else if let Some(bookmark) = bookmarks.slots[slot] {
+            // The zoom limits may have changed since the bookmark was stored
+            let zoom = settings.clamp_zoom(Vec3::splat(bookmark.zoom)).x;
+            flight.start(camera_transform, bookmark.position, zoom);
+        }
+    }
+}
+
+/// Clicking a machine group in the jump to panel flies the camera over all of its machines.
+pub fn jump_to_machine_group(
+    jump_buttons: Query<(&Interaction, &JumpButton), Changed<Interaction>>,
+    machines: Query<(&Transform, &PlacedMachine), Without<Camera2d>>,
+    windows: Res<Windows>,
+    settings: Res<Settings>,
+    mut flight: ResMut<CameraFlight>,
+    camera: Query<&Transform, With<Camera2d>>,
+) {
+    let window = if let Some(window) = windows.get_primary() {
+        window
+    } else {
+        return;
+    };
+
+    for (interaction, JumpButton(group)) in jump_buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let (_, group_machines) = MACHINE_GROUPS[*group];
+        let positions = machines
+            .iter()
+            .filter(|(_, placed_machine)| group_machines.contains(&placed_machine.machine))
+            .map(|(transform, _)| transform.translation.truncate())
+            .collect::<Vec<_>>();
+
+        if positions.is_empty() {
+            continue;
+        }
+
+        let camera_transform = camera.single();
+        let (center, zoom) =
+            frame_positions(positions, window, camera_transform.scale.x, &settings);
+
+        flight.start(camera_transform, center, zoom);
+    }
+}
+
+/// Moves the camera along the current flight, easing out towards its end.
+pub fn fly_camera(
+    time: Res<Time>,
+    mut flight: ResMut<CameraFlight>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    let (start_position, start_zoom, end_position, end_zoom) = match flight.path {
+        Some(path) => path,
+        None => return,
+    };
+
+    flight.timer.tick(time.delta());
+
+    let t = flight.timer.percent();
+    let eased = 1.0 - (1.0 - t).powi(3);
+
+    let mut camera_transform = camera.single_mut();
+    let zoom = start_zoom + (end_zoom - start_zoom) * eased;
+
+    camera_transform.translation = start_position
+        .lerp(end_position, eased)
+        .extend(camera_transform.translation.z);
+    camera_transform.scale = vec3(zoom, zoom, 1.0);
+
+    if flight.timer.finished() {
+        flight.path = None;
+    }
+}
+
+/// Only lists the groups with machines in them, and hides the panel while nothing is built.
+pub fn update_jump_list(
+    machines: Query<&PlacedMachine>,
+    mut jump_lists: Query<&mut Visibility, With<JumpList>>,
+    mut jump_buttons: Query<(&mut Style, &JumpButton)>,
+    mut labels: Query<(&mut Text, &JumpLabel)>,
+) {
+    let mut counts = [0; MACHINE_GROUPS.len()];
+
+    for placed_machine in machines.iter() {
+        if let Some(group) = MACHINE_GROUPS
+            .iter()
+            .position(|(_, group_machines)| group_machines.contains(&placed_machine.machine))
+        {
+            counts[group] += 1;
+        }
+    }
+
+    let any_machines = counts.iter().any(|count| *count > 0);
+
+    for mut visibility in jump_lists.iter_mut() {
+        if visibility.is_visible != any_machines {
+            visibility.is_visible = any_machines;
+        }
+    }
+
+    for (mut style, JumpButton(group)) in jump_buttons.iter_mut() {
+        let display = if counts[*group] > 0 {
+            Display::Flex
+        } else {
+            Display::None
+        };
+
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    for (mut text, JumpLabel(group)) in labels.iter_mut() {
+        let (name, _) = MACHINE_GROUPS[*group];
+        let value = format!("{} ({})", name, counts[*group]);
+
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraBookmark {
+    pub position: Vec2,
+    pub zoom: f32,
+}
+
+/// Views stored on the bookmark keys, kept between sessions.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct CameraBookmarks {
+    pub slots: [Option<CameraBookmark>; BOOKMARK_KEYS.len()],
+}
+
+impl CameraBookmarks {
+    /// One line per stored bookmark, e.g. `1 = 512 -256 4`.
+    pub fn encode(&self) -> String {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, bookmark)| {
+                bookmark.map(|bookmark| {
+                    format!(
+                        "{} = {} {} {}\n",
+                        slot + 1,
+                        bookmark.position.x,
+                        bookmark.position.y,
+                        bookmark.zoom
+                    )
+                })
+            })
+            .collect()
+    }
+
+    pub fn decode(encoded: &str) -> Result<CameraBookmarks, BookmarksDecodeError> {
+        let mut bookmarks = CameraBookmarks::default();
+
+        for line in encoded
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let invalid_line = || BookmarksDecodeError::InvalidLine(line.to_string());
+
+            let (slot, value) = line.split_once('=').ok_or_else(invalid_line)?;
+
+            let slot = slot
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|slot| (1..=BOOKMARK_KEYS.len()).contains(slot))
+                .ok_or_else(invalid_line)?;
+
+            let numbers = value
+                .split_whitespace()
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid_line())?;
+
+            match numbers[..] {
+                [x, y, zoom] if zoom > 0.0 => {
+                    bookmarks.slots[slot - 1] = Some(CameraBookmark {
+                        position: Vec2::new(x, y),
+                        zoom,
+                    });
+                }
+
+                _ => return Err(invalid_line()),
+            }
+        }
+
+        Ok(bookmarks)
+    }
+
+    /// Starts without bookmarks when there is no usable bookmarks file.
+    pub fn load() -> CameraBookmarks {
+        let encoded = match read_text_file(BOOKMARKS_FILE) {
+            Ok(encoded) => encoded,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return CameraBookmarks::default()
+            }
+            Err(error) => {
+                warn!("Could not read {}: {}", BOOKMARKS_FILE, error);
+                return CameraBookmarks::default();
+            }
+        };
+
+        CameraBookmarks::decode(&encoded).unwrap_or_else(|error| {
+            warn!("Could not load bookmarks: {:?}", error);
+            CameraBookmarks::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Err(error) = write_text_file(BOOKMARKS_FILE, &self.encode()) {
+            warn!("Could not write {}: {}", BOOKMARKS_FILE, error);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BookmarksDecodeError {
+    InvalidLine(String),
+}
+
+/// Camera movement towards a bookmark or machine group.
+#[derive(Resource)]
+pub struct CameraFlight {
+    /// Start position and zoom, then end position and zoom.
+    path: Option<(Vec2, f32, Vec2, f32)>,
+    timer: Timer,
+}
+
+impl Default for CameraFlight {
+    fn default() -> Self {
+        CameraFlight {
+            path: None,
+            timer: Timer::from_seconds(FLIGHT_DURATION, TimerMode::Once),
+        }
+    }
+}
+
+impl CameraFlight {
+    pub fn start(&mut self, camera_transform: &Transform, position: Vec2, zoom: f32) {
+        self.path = Some((
+            camera_transform.translation.truncate(),
+            camera_transform.scale.x,
+            position,
+            zoom,
+        ));
+        self.timer.reset();
+    }
+
+    /// Leaves the camera where the flight has taken it so far.
+    pub fn stop(&mut self) {
+        self.path = None;
+    }
+}
+
+#[derive(Component)]
+pub struct JumpList;
+
+/// Index of the machine group in the jump to panel.
+#[derive(Component)]
+pub struct JumpButton(usize);
+
+#[derive(Component)]
+pub struct JumpLabel(usize);
@@ -0,0 +1,338 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ninepatch::{NinePatchBundle, NinePatchData};
+
+use crate::{
+    assets::{Fonts, Images, NinePatches},
+    palette,
+};
+
+use super::{
+    input_map::{Action, InputMap},
+    settings::{SettingToggle, SettingToggledEvent, Settings},
+};
+
+pub fn setup_rebinding_screen(
+    mut commands: Commands,
+    images: Res<Images>,
+    fonts: Res<Fonts>,
+    ninepatches: Res<NinePatches>,
+) {
+    commands.insert_resource(Rebinding::default());
+
+    let content = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(16.0)),
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|content| {
+            content.spawn(TextBundle {
+                text: Text::from_section(
+                    "Controls",
+                    TextStyle {
+                        font: fonts.varela.clone(),
+                        color: palette::DARK_BLUE,
+                        font_size: 40.0,
+                    },
+                ),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            });
+
+            // Two columns, so all the actions fit on small windows
+            let columns = Action::list().chunks(Action::list().len().div_ceil(2));
+
+            content
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    },
+                    focus_policy: FocusPolicy::Pass,
+                    ..default()
+                })
+                .with_children(|table| {
+                    for actions in columns {
+                        table
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    margin: UiRect {
+                                        right: Val::Px(16.0),
+                                        ..default()
+                                    },
+                                    ..default()
+                                },
+                                focus_policy: FocusPolicy::Pass,
+                                ..default()
+                            })
+                            .with_children(|column| {
+                                for action in actions {
+                                    column
+                                        .spawn(ButtonBundle {
+                                            style: Style {
+                                                padding: UiRect::all(Val::Px(4.0)),
+                                                ..default()
+                                            },
+                                            background_color: Color::NONE.into(),
+                                            ..default()
+                                        })
+                                        .with_children(|row| {
+                                            row.spawn(TextBundle {
+                                                text: Text::from_sections([
+                                                    TextSection::new(
+                                                        format!("{}: ", action.name()),
+                                                        TextStyle {
+                                                            font: fonts.varela.clone(),
+                                                            color: palette::LIGHT_BROWN,
+                                                            font_size: 24.0,
+                                                        },
+                                                    ),
+                                                    TextSection::new(
+                                                        "",
+                                                        TextStyle {
+                                                            font: fonts.varela.clone(),
+                                                            color: palette::DARK_BLUE,
+                                                            font_size: 24.0,
+                                                        },
+                                                    ),
+                                                ]),
+                                                focus_policy: FocusPolicy::Pass,
+                                                ..default()
+                                            })
+                                            .insert(RebindLabel(*action));
+                                        })
+                                        .insert(RebindButton(*action));
+                                }
+                            });
+                    }
+                });
+
+            for toggle in SettingToggle::list() {
+                content
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(TextBundle {
+                            text: Text::from_sections([
+                                TextSection::new(
+                                    format!("{}: ", toggle.name()),
+                                    TextStyle {
+                                        font: fonts.varela.clone(),
+                                        color: palette::LIGHT_BROWN,
+                                        font_size: 24.0,
+                                    },
+                                ),
+                                TextSection::new(
+                                    "",
+                                    TextStyle {
+                                        font: fonts.varela.clone(),
+                                        color: palette::DARK_BLUE,
+                                        font_size: 24.0,
+                                    },
+                                ),
+                            ]),
+                            focus_policy: FocusPolicy::Pass,
+                            ..default()
+                        })
+                        .insert(SettingLabel(*toggle));
+                    })
+                    .insert(SettingButton(*toggle));
+            }
+
+            content.spawn(TextBundle {
+                text: Text::from_section(
+                    "Click an action, then press a mouse button or key.\n\
+                     Click a setting to switch it.\n\
+                     Backspace: restore defaults, F1: close",
+                    TextStyle {
+                        font: fonts.varela.clone(),
+                        color: palette::LIGHT_BROWN,
+                        font_size: 18.0,
+                    },
+                ),
+                style: Style {
+                    margin: UiRect {
+                        top: Val::Px(12.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            });
+        })
+        .insert(Name::new("Rebinding Content"))
+        .id();
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.188, 0.208, 0.278, 0.4).into(),
+            visibility: Visibility { is_visible: false },
+            z_index: ZIndex::Global(10),
+            ..default()
+        })
+        .with_children(|screen| {
+            screen.spawn(NinePatchBundle {
+                nine_patch_data: NinePatchData::with_single_content(
+                    images.panel.clone(),
+                    ninepatches.panel.clone(),
+                    content,
+                ),
+                ..default()
+            });
+        })
+        .insert(Name::new("Rebinding Screen"))
+        .insert(RebindingScreen);
+}
+
+/// F1 opens the screen. While it is open, gameplay does not see any key or button presses.
+pub fn rebind_actions(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut input_map: ResMut<InputMap>,
+    mut settings: ResMut<Settings>,
+    rebind_buttons: Query<(&Interaction, &RebindButton), Changed<Interaction>>,
+    setting_buttons: Query<(&Interaction, &SettingButton), Changed<Interaction>>,
+    mut setting_toggled_events: EventWriter<SettingToggledEvent>,
+) {
+    if keys.just_pressed(KeyCode::F1) {
+        if rebinding.capturing.is_some() {
+            rebinding.capturing = None;
+        } else {
+            rebinding.open = !rebinding.open;
+        }
+
+        keys.clear();
+        return;
+    }
+
+    if !rebinding.open {
+        return;
+    }
+
+    if let Some(action) = rebinding.capturing {
+        let pressed_key = keys
+            .get_just_pressed()
+            .copied()
+            .find(|key| InputMap::is_bindable_key(*key) && action.accepts_key());
+        let pressed_button = buttons
+            .get_just_pressed()
+            .copied()
+            .find(|button| InputMap::is_bindable_button(*button) && action.accepts_mouse());
+
+        if let Some(key) = pressed_key {
+            input_map.bind_key(action, key);
+        } else if let Some(button) = pressed_button {
+            input_map.bind_mouse(action, button);
+        }
+
+        if pressed_key.is_some() || pressed_button.is_some() {
+            input_map.save();
+            rebinding.capturing = None;
+        }
+    } else {
+        if keys.just_pressed(KeyCode::Escape) {
+            rebinding.open = false;
+        } else if keys.just_pressed(KeyCode::Back) {
+            *input_map = InputMap::default();
+            input_map.save();
+        }
+
+        for (interaction, RebindButton(action)) in rebind_buttons.iter() {
+            if *interaction == Interaction::Clicked {
+                rebinding.capturing = Some(*action);
+            }
+        }
+
+        for (interaction, SettingButton(toggle)) in setting_buttons.iter() {
+            if *interaction == Interaction::Clicked {
+                toggle.toggle(&mut settings);
+                settings.save();
+                setting_toggled_events.send(SettingToggledEvent(*toggle));
+            }
+        }
+    }
+
+    keys.clear();
+    buttons.clear();
+}
+
+pub fn update_rebinding_screen(
+    rebinding: Res<Rebinding>,
+    input_map: Res<InputMap>,
+    settings: Res<Settings>,
+    mut screens: Query<&mut Visibility, With<RebindingScreen>>,
+    mut labels: Query<(&mut Text, &RebindLabel), Without<SettingLabel>>,
+    mut setting_labels: Query<(&mut Text, &SettingLabel), Without<RebindLabel>>,
+) {
+    for mut visibility in screens.iter_mut() {
+        if visibility.is_visible != rebinding.open {
+            visibility.is_visible = rebinding.open;
+        }
+    }
+
+    if !rebinding.open {
+        return;
+    }
+
+    for (mut text, RebindLabel(action)) in labels.iter_mut() {
+        let label = if rebinding.capturing == Some(*action) {
+            "press a button or key...".to_string()
+        } else {
+            input_map.binding(*action).describe()
+        };
+
+        if text.sections[1].value != label {
+            text.sections[1].value = label;
+        }
+    }
+
+    for (mut text, SettingLabel(toggle)) in setting_labels.iter_mut() {
+        let label = if toggle.get(&settings) { "On" } else { "Off" };
+
+        if text.sections[1].value != label {
+            text.sections[1].value = label.to_string();
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub open: bool,
+    /// Action waiting for the next button or key press.
+    pub capturing: Option<Action>,
+}
+
+#[derive(Component)]
+pub struct RebindingScreen;
+
+#[derive(Component)]
+pub struct RebindButton(pub Action);
+
+#[derive(Component)]
+pub struct RebindLabel(pub Action);
+
+#[derive(Component)]
+pub struct SettingButton(pub SettingToggle);
+
+#[derive(Component)]
+pub struct SettingLabel(pub SettingToggle);
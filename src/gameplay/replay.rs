@@ -0,0 +1,585 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::{app::AppExit, prelude::*, time::TimeUpdateStrategy, utils::Instant};
+
+use super::{
+    components::GameRng,
+    hud::{Toolbar, ToolbarButtonSelectedEvent},
+    input::{WorldMouse, WorldMouseEvent},
+    input_map::{InputMap, InputMapDecodeError},
+    settings::{
+        read_text_file, write_text_file, SettingToggle, SettingToggledEvent, Settings,
+        SettingsDecodeError,
+    },
+};
+
+/// Records the session to the file at this path.
+const RECORD_VAR: &str = "ONE_CLICKER_RECORD";
+/// Replays the recording at this path instead of reading the player's input.
+const REPLAY_VAR: &str = "ONE_CLICKER_REPLAY";
+
+/// Ticks between rewrites of the recording file, so a crash loses little of it.
+const FLUSH_TICKS: u64 = 300;
+
+/// A recording starts with the seed of the game RNG and the bindings and settings
+/// the player had, followed by one line per input in the order they happened,
+/// each prefixed with its tick:
+///
+/// ```text
+/// seed 42
+/// binding place = mouse:Left
+/// setting hover_collection = true
+/// 0 dt 16666667
+/// 0 key press Space
+/// 0 mouse click Left 120.5 -30
+/// 0 mouse drag Left 0 0 256 0
+/// 0 select 3
+/// 0 toggle edge_scrolling
+/// ```
+///
+/// The recorded bindings and settings are used until the replay ends, then the player's come back.
+pub fn setup_replay(
+    mut replay: ResMut<Replay>,
+    mut rng: ResMut<GameRng>,
+    mut input_map: ResMut<InputMap>,
+    mut settings: ResMut<Settings>,
+) {
+    if let Ok(path) = std::env::var(REPLAY_VAR) {
+        let recording = read_text_file(&path)
+            .map_err(|error| format!("{}", error))
+            .and_then(|encoded| {
+                Recording::decode(&encoded).map_err(|error| format!("{:?}", error))
+            });
+
+        match recording {
+            Ok(recording) => {
+                info!("Replaying {} with seed {}", path, recording.seed);
+
+                *rng = GameRng::new(recording.seed);
+                replay.mode = ReplayMode::Replaying {
+                    entries: recording.entries.into(),
+                    keys: Input::default(),
+                    player_input_map: std::mem::replace(&mut *input_map, recording.input_map),
+                    player_settings: std::mem::replace(&mut *settings, recording.settings),
+                };
+            }
+
+            Err(error) => warn!("Could not load replay {}: {}", path, error),
+        }
+    } else if let Ok(path) = std::env::var(RECORD_VAR) {
+        info!("Recording to {} with seed {}", path, rng.seed());
+
+        replay.mode = ReplayMode::Recording {
+            path,
+            recording: Recording {
+                seed: rng.seed(),
+                input_map: input_map.clone(),
+                settings: settings.clone(),
+                entries: Vec::new(),
+            },
+        };
+    }
+}
+
+/// Replaces the keyboard with the recorded keys, sends the recorded world mouse events
+/// and switches the recorded settings.
+/// Mouse buttons are released so the player cannot click through the UI meanwhile.
+pub fn replay_inputs(
+    mut replay: ResMut<Replay>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut settings: ResMut<Settings>,
+    mut world_mouse: ResMut<WorldMouse>,
+    mut world_mouse_events: EventWriter<WorldMouseEvent>,
+) {
+    let tick = match replay.tick {
+        Some(tick) => tick,
+        None => return,
+    };
+
+    let (entries, replay_keys) = match &mut replay.mode {
+        ReplayMode::Replaying { entries, keys, .. } => (entries, keys),
+        _ => return,
+    };
+
+    replay_keys.clear();
+
+    for (_, entry) in entries
+        .iter()
+        .take_while(|(entry_tick, _)| *entry_tick == tick)
+    {
+        match *entry {
+            ReplayEntry::Key { key, pressed: true } => replay_keys.press(key),
+            ReplayEntry::Key {
+                key,
+                pressed: false,
+            } => replay_keys.release(key),
+
+            ReplayEntry::Mouse(event) => {
+                if let WorldMouseEvent::Hover { position } = event {
+                    world_mouse.position_world = position;
+                }
+
+                world_mouse_events.send(event);
+            }
+
+            ReplayEntry::Toggle(toggle) => toggle.toggle(&mut settings),
+
+            _ => {}
+        }
+    }
+
+    *keys = replay_keys.clone();
+    buttons.reset_all();
+}
+
+/// Toolbar selections come from many places, some of which are not replayed, like UI clicks.
+/// Instead of the selections made this tick, the recorded ones are sent.
+pub fn replay_selections(
+    replay: Res<Replay>,
+    toolbars: Query<&Children, With<Toolbar>>,
+    mut button_selected_events: ResMut<Events<ToolbarButtonSelectedEvent>>,
+) {
+    let (tick, entries) = match (replay.tick, &replay.mode) {
+        (Some(tick), ReplayMode::Replaying { entries, .. }) => (tick, entries),
+        _ => return,
+    };
+
+    button_selected_events.clear();
+
+    let toolbar_entries = if let Ok(toolbar_entries) = toolbars.get_single() {
+        toolbar_entries
+    } else {
+        return;
+    };
+
+    for (_, entry) in entries
+        .iter()
+        .take_while(|(entry_tick, _)| *entry_tick == tick)
+    {
+        if let ReplayEntry::Select(selected) = *entry {
+            let selected = selected.and_then(|index| toolbar_entries.get(index).copied());
+            button_selected_events.send(ToolbarButtonSelectedEvent(selected));
+        }
+    }
+}
+
+pub fn record_inputs(
+    mut replay: ResMut<Replay>,
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    toolbars: Query<&Children, With<Toolbar>>,
+    mut world_mouse_events: EventReader<WorldMouseEvent>,
+    mut button_selected_events: EventReader<ToolbarButtonSelectedEvent>,
+    mut setting_toggled_events: EventReader<SettingToggledEvent>,
+) {
+    let tick = match replay.tick {
+        Some(tick) => tick,
+        None => {
+            world_mouse_events.clear();
+            button_selected_events.clear();
+            setting_toggled_events.clear();
+            return;
+        }
+    };
+
+    let entries = match &mut replay.mode {
+        ReplayMode::Recording { recording, .. } => &mut recording.entries,
+        _ => {
+            world_mouse_events.clear();
+            button_selected_events.clear();
+            setting_toggled_events.clear();
+            return;
+        }
+    };
+
+    entries.push((tick, ReplayEntry::Delta(time.delta())));
+
+    // Other keys do nothing in the game, and could not be read back
+    let named = |key: &&KeyCode| InputMap::is_named_key(**key);
+
+    for &key in keys.get_just_released().filter(named) {
+        entries.push((
+            tick,
+            ReplayEntry::Key {
+                key,
+                pressed: false,
+            },
+        ));
+    }
+
+    for &key in keys.get_just_pressed().filter(named) {
+        entries.push((tick, ReplayEntry::Key { key, pressed: true }));
+    }
+
+    for event in world_mouse_events.iter() {
+        entries.push((tick, ReplayEntry::Mouse(*event)));
+    }
+
+    let toolbar_entries = toolbars.get_single().ok();
+
+    for &ToolbarButtonSelectedEvent(selected) in button_selected_events.iter() {
+        let index = match (selected, toolbar_entries) {
+            (None, _) => None,
+            (Some(entity), Some(toolbar_entries)) => {
+                match toolbar_entries.iter().position(|&entry| entry == entity) {
+                    Some(index) => Some(index),
+                    None => continue,
+                }
+            }
+            (Some(_), None) => continue,
+        };
+
+        entries.push((tick, ReplayEntry::Select(index)));
+    }
+
+    for &SettingToggledEvent(toggle) in setting_toggled_events.iter() {
+        entries.push((tick, ReplayEntry::Toggle(toggle)));
+    }
+}
+
+/// Moves on to the next tick. A replay also sets the frame time of the next tick
+/// to the recorded one, so timers run exactly as they did while recording.
+pub fn advance_replay(
+    mut replay: ResMut<Replay>,
+    mut input_map: ResMut<InputMap>,
+    mut settings: ResMut<Settings>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+    time: Res<Time>,
+    mut exit_events: EventReader<AppExit>,
+) {
+    let next_tick = replay.tick.map_or(0, |tick| tick + 1);
+    replay.tick = Some(next_tick);
+
+    let exiting = exit_events.iter().last().is_some();
+
+    let finished = match &mut replay.mode {
+        ReplayMode::Off => false,
+
+        ReplayMode::Recording { path, recording } => {
+            if exiting || next_tick.is_multiple_of(FLUSH_TICKS) {
+                if let Err(error) = write_text_file(path, &recording.encode()) {
+                    warn!("Could not write recording {}: {}", path, error);
+                }
+            }
+
+            false
+        }
+
+        ReplayMode::Replaying { entries, .. } => {
+            while matches!(entries.front(), Some((tick, _)) if *tick < next_tick) {
+                entries.pop_front();
+            }
+
+            let delta = entries
+                .iter()
+                .take_while(|(tick, _)| *tick == next_tick)
+                .find_map(|(_, entry)| match entry {
+                    ReplayEntry::Delta(delta) => Some(*delta),
+                    _ => None,
+                });
+
+            if let Some(delta) = delta {
+                let last_update = time.last_update().unwrap_or_else(Instant::now);
+                *time_update_strategy = TimeUpdateStrategy::ManualInstant(last_update + delta);
+            }
+
+            entries.is_empty()
+        }
+    };
+
+    if finished {
+        info!("Replay finished");
+
+        *time_update_strategy = TimeUpdateStrategy::Automatic;
+
+        if let ReplayMode::Replaying {
+            player_input_map,
+            player_settings,
+            ..
+        } = std::mem::take(&mut replay.mode)
+        {
+            *input_map = player_input_map;
+            *settings = player_settings;
+        }
+    }
+}
+
+pub fn replaying(replay: Res<Replay>) -> bool {
+    matches!(replay.mode, ReplayMode::Replaying { .. })
+}
+
+#[derive(Resource, Default)]
+pub struct Replay {
+    mode: ReplayMode,
+    /// Frames since gameplay started, counted from the first full frame.
+    tick: Option<u64>,
+}
+
+#[derive(Default)]
+enum ReplayMode {
+    #[default]
+    Off,
+    Recording {
+        path: String,
+        recording: Recording,
+    },
+    Replaying {
+        entries: VecDeque<(u64, ReplayEntry)>,
+        /// Keyboard state built from the recorded presses and releases.
+        keys: Input<KeyCode>,
+        /// Bindings and settings to give back once the replay ends.
+        player_input_map: InputMap,
+        player_settings: Settings,
+    },
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ReplayEntry {
+    /// Frame time of the tick.
+    Delta(Duration),
+    Key {
+        key: KeyCode,
+        pressed: bool,
+    },
+    Mouse(WorldMouseEvent),
+    /// Index of the selected toolbar entry, none when the tool was dropped.
+    Select(Option<usize>),
+    /// Setting switched on the controls screen.
+    Toggle(SettingToggle),
+}
+
+struct Recording {
+    seed: u64,
+    input_map: InputMap,
+    settings: Settings,
+    entries: Vec<(u64, ReplayEntry)>,
+}
+
+impl Recording {
+    fn encode(&self) -> String {
+        let mut encoded = format!("seed {}\n", self.seed);
+
+        for line in self.input_map.encode().lines() {
+            encoded.push_str(&format!("binding {}\n", line));
+        }
+
+        for line in self.settings.encode().lines() {
+            encoded.push_str(&format!("setting {}\n", line));
+        }
+
+        for (tick, entry) in &self.entries {
+            let line = match entry {
+                ReplayEntry::Delta(delta) => format!("dt {}", delta.as_nanos()),
+                ReplayEntry::Key { key, pressed } => format!(
+                    "key {} {:?}",
+                    if *pressed { "press" } else { "release" },
+                    key
+                ),
+                ReplayEntry::Mouse(event) => match event {
+                    WorldMouseEvent::Click { button, position } => {
+                        format!("mouse click {:?} {} {}", button, position.x, position.y)
+                    }
+                    WorldMouseEvent::Hover { position } => {
+                        format!("mouse hover {} {}", position.x, position.y)
+                    }
+                    WorldMouseEvent::Drag {
+                        button,
+                        start_world,
+                        end_world,
+                    } => format!(
+                        "mouse drag {:?} {} {} {} {}",
+                        button, start_world.x, start_world.y, end_world.x, end_world.y
+                    ),
+                    WorldMouseEvent::DragEnd {
+                        button,
+                        start_world,
+                        end_world,
+                    } => format!(
+                        "mouse drag_end {:?} {} {} {} {}",
+                        button, start_world.x, start_world.y, end_world.x, end_world.y
+                    ),
+                },
+                ReplayEntry::Select(Some(index)) => format!("select {}", index),
+                ReplayEntry::Select(None) => "select none".to_string(),
+                ReplayEntry::Toggle(toggle) => format!("toggle {}", toggle.id()),
+            };
+
+            encoded.push_str(&format!("{} {}\n", tick, line));
+        }
+
+        encoded
+    }
+
+    fn decode(encoded: &str) -> Result<Recording, RecordingDecodeError> {
+        let mut lines = encoded
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+
+        let seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed "))
+            .and_then(|seed| seed.trim().parse().ok())
+            .ok_or(RecordingDecodeError::MissingSeed)?;
+
+        let mut bindings = String::new();
+        let mut settings = String::new();
+        let mut entries = Vec::new();
+
+        for line in lines {
+            if let Some(binding) = line.strip_prefix("binding ") {
+                bindings.push_str(binding);
+                bindings.push('\n');
+                continue;
+            }
+
+            if let Some(setting) = line.strip_prefix("setting ") {
+                settings.push_str(setting);
+                settings.push('\n');
+                continue;
+            }
+
+            let invalid_line = || RecordingDecodeError::InvalidLine(line.to_string());
+
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            let tick = tokens
+                .first()
+                .and_then(|tick| tick.parse().ok())
+                .ok_or_else(invalid_line)?;
+
+            let float = |index: usize| -> Result<f32, RecordingDecodeError> {
+                tokens
+                    .get(index)
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(invalid_line)
+            };
+            let point = |index: usize| -> Result<Vec2, RecordingDecodeError> {
+                Ok(Vec2::new(float(index)?, float(index + 1)?))
+            };
+            let button = |index: usize| -> Result<MouseButton, RecordingDecodeError> {
+                tokens
+                    .get(index)
+                    .and_then(|name| InputMap::button_from_name(name))
+                    .ok_or_else(invalid_line)
+            };
+
+            let entry = match tokens[1..] {
+                ["dt", nanos] => ReplayEntry::Delta(Duration::from_nanos(
+                    nanos.parse().map_err(|_| invalid_line())?,
+                )),
+
+                ["key", action, name] => ReplayEntry::Key {
+                    key: InputMap::key_from_name(name).ok_or_else(invalid_line)?,
+                    pressed: match action {
+                        "press" => true,
+                        "release" => false,
+                        _ => return Err(invalid_line()),
+                    },
+                },
+
+                ["mouse", "click", _, _, _] => ReplayEntry::Mouse(WorldMouseEvent::Click {
+                    button: button(3)?,
+                    position: point(4)?,
+                }),
+
+                ["mouse", "hover", _, _] => ReplayEntry::Mouse(WorldMouseEvent::Hover {
+                    position: point(3)?,
+                }),
+
+                ["mouse", "drag", _, _, _, _, _] => ReplayEntry::Mouse(WorldMouseEvent::Drag {
+                    button: button(3)?,
+                    start_world: point(4)?,
+                    end_world: point(6)?,
+                }),
+
+                ["mouse", "drag_end", _, _, _, _, _] => {
+                    ReplayEntry::Mouse(WorldMouseEvent::DragEnd {
+                        button: button(3)?,
+                        start_world: point(4)?,
+                        end_world: point(6)?,
+                    })
+                }
+
+                ["select", "none"] => ReplayEntry::Select(None),
+                ["select", index] => {
+                    ReplayEntry::Select(Some(index.parse().map_err(|_| invalid_line())?))
+                }
+
+                ["toggle", id] => ReplayEntry::Toggle(
+                    SettingToggle::list()
+                        .iter()
+                        .copied()
+                        .find(|toggle| toggle.id() == id)
+                        .ok_or_else(invalid_line)?,
+                ),
+
+                _ => return Err(invalid_line()),
+            };
+
+            entries.push((tick, entry));
+        }
+
+        // Recordings without bindings or settings were made with the defaults
+        let input_map = InputMap::decode(&bindings).map_err(RecordingDecodeError::InputMap)?;
+        let settings = Settings::decode(&settings).map_err(RecordingDecodeError::Settings)?;
+
+        Ok(Recording {
+            seed,
+            input_map,
+            settings,
+            entries,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingDecodeError {
+    MissingSeed,
+    InvalidLine(String),
+    InputMap(InputMapDecodeError),
+    Settings(SettingsDecodeError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::input_map::Action;
+
+    #[test]
+    fn recording_keeps_bindings_settings_and_toggles() {
+        let mut input_map = InputMap::default();
+        input_map.bind_key(Action::Rotate, KeyCode::F);
+
+        let settings = Settings {
+            edge_scrolling: true,
+            refund_percent: 75,
+            ..default()
+        };
+
+        let recording = Recording {
+            seed: 7,
+            input_map: input_map.clone(),
+            settings: settings.clone(),
+            entries: vec![(3, ReplayEntry::Toggle(SettingToggle::HoverCollection))],
+        };
+
+        let decoded = Recording::decode(&recording.encode()).unwrap();
+
+        assert_eq!(decoded.seed, 7);
+        assert_eq!(decoded.input_map, input_map);
+        assert_eq!(decoded.settings, settings);
+        assert!(matches!(
+            decoded.entries[..],
+            [(3, ReplayEntry::Toggle(SettingToggle::HoverCollection))]
+        ));
+    }
+
+    #[test]
+    fn recording_without_header_uses_defaults() {
+        let decoded = Recording::decode("seed 1\n0 dt 16666667\n").unwrap();
+
+        assert_eq!(decoded.input_map, InputMap::default());
+        assert_eq!(decoded.settings, Settings::default());
+    }
+}
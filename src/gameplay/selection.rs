@@ -0,0 +1,322 @@
+use bevy::prelude::*;
+
+use crate::{
+    assets::{Fonts, Images},
+    palette,
+};
+
+use super::{
+    blueprints::{Blueprint, BlueprintClipboard},
+    history::{Edit, EditHistory},
+    hud::{ToolbarButtonPaste, ToolbarButtonSelectedEvent},
+    input::{WorldMouse, WorldMouseEvent},
+    input_map::{Action, InputMap},
+    machines::{
+        request_spot_updates, spawn_placed_machine, MachineDeleteRequest, PlacedMachine,
+        UpdateSpotsRequest,
+    },
+    tile_tracked_entities::TilePosition,
+    TILE_SIZE,
+};
+
+pub fn setup_selection(mut commands: Commands, fonts: Res<Fonts>) {
+    commands.insert_resource(Selection::default());
+
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: palette::SELECTION,
+                custom_size: Some(Vec2::splat(TILE_SIZE)),
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .with_children(|selection_box| {
+            selection_box
+                .spawn(Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: fonts.varela.clone(),
+                            color: palette::DARK_BLUE,
+                            font_size: 64.0,
+                        },
+                    )
+                    .with_alignment(TextAlignment::BOTTOM_CENTER),
+                    ..default()
+                })
+                .insert(SelectionHint);
+        })
+        .insert(Name::new("Selection Box"))
+        .insert(SelectionBox);
+}
+
+/// Dragging with Shift held selects machines instead of using the current tool.
+pub fn is_box_selecting(keys: &Input<KeyCode>) -> bool {
+    keys.any_pressed([KeyCode::LShift, KeyCode::RShift])
+}
+
+pub fn box_select_machines(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut world_mouse_events: EventReader<WorldMouseEvent>,
+    mut selection: ResMut<Selection>,
+    machines: Query<(Entity, &Transform), With<PlacedMachine>>,
+    selected_machines: Query<Entity, With<Selected>>,
+) {
+    for event in world_mouse_events.iter() {
+        match event {
+            WorldMouseEvent::DragEnd {
+                button,
+                start_world,
+                end_world,
+            } if input_map.is_button(Action::Place, *button) && is_box_selecting(&keys) => {
+                for entity in selected_machines.iter() {
+                    commands.entity(entity).remove::<Selected>();
+                }
+
+                let start_tile = TilePosition::from_world(*start_world);
+                let end_tile = TilePosition::from_world(*end_world);
+                let area = SelectionArea::new(start_tile, end_tile);
+
+                let mut count = 0;
+
+                for (entity, transform) in machines.iter() {
+                    if area.contains(TilePosition::from_world(transform.translation.truncate())) {
+                        commands.entity(entity).insert(Selected);
+                        count += 1;
+                    }
+                }
+
+                selection.area = if count > 0 { Some(area) } else { None };
+            }
+
+            WorldMouseEvent::Click { button, .. }
+                if input_map.is_button(Action::Place, *button)
+                    && selection.area.is_some()
+                    && !is_box_selecting(&keys) =>
+            {
+                for entity in selected_machines.iter() {
+                    commands.entity(entity).remove::<Selected>();
+                }
+
+                selection.area = None;
+            }
+
+            _ => (),
+        }
+    }
+}
+
+pub fn selection_actions(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    images: Res<Images>,
+    mut selection: ResMut<Selection>,
+    mut selected_machines: Query<(Entity, &Transform, &mut PlacedMachine), With<Selected>>,
+    paste_buttons: Query<Entity, With<ToolbarButtonPaste>>,
+    mut clipboard: ResMut<BlueprintClipboard>,
+    mut history: ResMut<EditHistory>,
+    mut machine_delete_requests: EventWriter<MachineDeleteRequest>,
+    mut update_spots_requests: EventWriter<UpdateSpotsRequest>,
+    mut button_selected_events: EventWriter<ToolbarButtonSelectedEvent>,
+) {
+    let area = if let Some(area) = selection.area {
+        area
+    } else {
+        return;
+    };
+
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    // Backspace stays a fixed second key for deleting, like the arrow keys for panning
+    if input_map.just_pressed(Action::DeleteSelection, &keys) || keys.just_pressed(KeyCode::Back) {
+        for (_, transform, _) in selected_machines.iter() {
+            machine_delete_requests.send(MachineDeleteRequest {
+                position: TilePosition::from_world(transform.translation.truncate()),
+                history_refund: None,
+                confirmed: true,
+            });
+        }
+
+        selection.area = None;
+    } else if input_map.just_pressed(Action::Rotate, &keys) && !ctrl {
+        for (entity, transform, placed_machine) in selected_machines.iter() {
+            let machine = placed_machine.machine;
+            let rotated = machine.rotated();
+
+            if rotated == machine {
+                continue;
+            }
+
+            let position = TilePosition::from_world(transform.translation.truncate());
+
+            commands.entity(entity).despawn_recursive();
+
+            // The rotated machine stays turned off and keeps counting where the old one stopped
+            let rotated_entity = spawn_placed_machine(
+                &mut commands,
+                &images,
+                PlacedMachine {
+                    enabled: placed_machine.enabled,
+                    stats: placed_machine.stats,
+                    ..PlacedMachine::new(rotated)
+                },
+                position,
+            );
+            commands.entity(rotated_entity).insert(Selected);
+
+            history.record(Edit::Delete {
+                machine,
+                position,
+                refund: 0,
+                enabled: placed_machine.enabled,
+            });
+            history.record(Edit::Place {
+                machine: rotated,
+                position,
+                cost: 0,
+                enabled: placed_machine.enabled,
+            });
+
+            request_spot_updates(position, &mut update_spots_requests);
+        }
+    } else if input_map.just_pressed(Action::Toggle, &keys) {
+        for (_, _, mut placed_machine) in selected_machines.iter_mut() {
+            placed_machine.enabled = !placed_machine.enabled;
+        }
+    } else if input_map.just_pressed(Action::Copy, &keys) && ctrl {
+        let blueprint = Blueprint::capture(
+            area.min,
+            area.max,
+            selected_machines
+                .iter()
+                .map(|(_, transform, placed_machine)| (transform, placed_machine)),
+        );
+
+        clipboard.blueprint = Some(blueprint);
+
+        if let Ok(paste_button) = paste_buttons.get_single() {
+            button_selected_events.send(ToolbarButtonSelectedEvent(Some(paste_button)));
+        }
+    } else if input_map.just_pressed(Action::Cancel, &keys) {
+        for (entity, _, _) in selected_machines.iter() {
+            commands.entity(entity).remove::<Selected>();
+        }
+
+        selection.area = None;
+    }
+}
+
+type SelectionHints<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Text, &'static mut Transform),
+    (With<SelectionHint>, Without<SelectionBox>),
+>;
+
+pub fn update_selection_box(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    world_mouse: Res<WorldMouse>,
+    selection: Res<Selection>,
+    selected_machines: Query<(), With<Selected>>,
+    mut selection_boxes: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<SelectionBox>>,
+    mut selection_hints: SelectionHints,
+) {
+    let dragged_area = match input_map.drag_start(Action::Place, &world_mouse) {
+        Some(start_world) if is_box_selecting(&keys) => Some(SelectionArea::new(
+            TilePosition::from_world(start_world),
+            TilePosition::from_world(world_mouse.position_world),
+        )),
+        _ => None,
+    };
+
+    let (area, hint) = match (dragged_area, selection.area) {
+        (Some(area), _) => (Some(area), String::new()),
+        (None, Some(area)) => (
+            Some(area),
+            format!(
+                "{} selected\n{}: delete, {}: rotate, {}: toggle, Ctrl+{}: copy",
+                selected_machines.iter().count(),
+                input_map.binding(Action::DeleteSelection).describe(),
+                input_map.binding(Action::Rotate).describe(),
+                input_map.binding(Action::Toggle).describe(),
+                input_map.binding(Action::Copy).describe(),
+            ),
+        ),
+        (None, None) => (None, String::new()),
+    };
+
+    for (mut sprite, mut transform, mut visibility) in selection_boxes.iter_mut() {
+        visibility.is_visible = area.is_some();
+
+        if let Some(area) = area {
+            let size = (area.max.to_vec() - area.min.to_vec() + Vec2::ONE) * TILE_SIZE;
+
+            sprite.custom_size = Some(size);
+            transform.translation = (area.min.to_world() + size / 2.0).extend(0.5);
+
+            for (mut text, mut hint_transform) in selection_hints.iter_mut() {
+                if text.sections[0].value != hint {
+                    text.sections[0].value = hint.clone();
+                }
+
+                hint_transform.translation = Vec3::new(0.0, size.y / 2.0, 0.1);
+            }
+        }
+    }
+}
+
+/// Highlights selected machines and greys out disabled ones.
+pub fn tint_machines(mut machines: Query<(&mut Sprite, &PlacedMachine, Option<&Selected>)>) {
+    for (mut sprite, placed_machine, selected) in machines.iter_mut() {
+        let color = if selected.is_some() {
+            palette::SELECTED
+        } else if !placed_machine.enabled {
+            palette::DISABLED
+        } else {
+            Color::WHITE
+        };
+
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SelectionArea {
+    pub min: TilePosition,
+    pub max: TilePosition,
+}
+
+impl SelectionArea {
+    pub fn new(corner_a: TilePosition, corner_b: TilePosition) -> SelectionArea {
+        SelectionArea {
+            min: TilePosition::new(corner_a.x.min(corner_b.x), corner_a.y.min(corner_b.y)),
+            max: TilePosition::new(corner_a.x.max(corner_b.x), corner_a.y.max(corner_b.y)),
+        }
+    }
+
+    pub fn contains(&self, tile: TilePosition) -> bool {
+        tile.x >= self.min.x && tile.x <= self.max.x && tile.y >= self.min.y && tile.y <= self.max.y
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub area: Option<SelectionArea>,
+}
+
+#[derive(Component)]
+pub struct Selected;
+
+#[derive(Component)]
+pub struct SelectionBox;
+
+#[derive(Component)]
+pub struct SelectionHint;
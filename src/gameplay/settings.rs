@@ -0,0 +1,308 @@
+use std::io;
+
+use bevy::prelude::*;
+
+use super::{components::Currency, machines::Machine};
+
+const SETTINGS_FILE: &str = "settings.cfg";
+
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Pan the camera while the cursor rests on an edge of the window.
+    pub edge_scrolling: bool,
+    /// Smallest camera scale, the closest the camera gets.
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// Zoom change per scrolled line, for mouse wheels.
+    pub line_zoom_sensitivity: f32,
+    /// Zoom change per scrolled pixel, for touchpads.
+    pub pixel_zoom_sensitivity: f32,
+    /// Longest press, in seconds, that still counts as a click.
+    pub click_duration: f64,
+    /// Farthest the cursor can move during a click, in window pixels.
+    pub click_distance: f32,
+    /// Holding the place button still keeps clicking instead of starting a drag.
+    pub hold_to_click: bool,
+    /// Clicks per second while holding.
+    pub hold_click_rate: f64,
+    /// Collect coins by moving the cursor over them, otherwise by clicking or dragging over them.
+    pub hover_collection: bool,
+    /// Share of the machine cost returned to the player when the machine is deleted.
+    pub refund_percent: Currency,
+    /// Deleting machines at least this expensive has to be confirmed with a second click.
+    pub confirm_delete_cost: Currency,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            edge_scrolling: false,
+            min_zoom: 1.0,
+            max_zoom: 20.0,
+            line_zoom_sensitivity: 0.2,
+            pixel_zoom_sensitivity: 0.002,
+            click_duration: 0.2,
+            click_distance: 10.0,
+            hold_to_click: false,
+            hold_click_rate: 8.0,
+            hover_collection: true,
+            refund_percent: 50,
+            confirm_delete_cost: 500,
+        }
+    }
+}
+
+impl Settings {
+    pub fn clamp_zoom(&self, scale: Vec3) -> Vec3 {
+        scale.clamp(
+            Vec3::new(self.min_zoom, self.min_zoom, 1.0),
+            Vec3::new(self.max_zoom, self.max_zoom, 1.0),
+        )
+    }
+
+    pub fn refund(&self, machine: Machine) -> Currency {
+        machine.cost() * self.refund_percent / 100
+    }
+
+    /// One line per setting, e.g. `edge_scrolling = true`.
+    pub fn encode(&self) -> String {
+        format!(
+            "edge_scrolling = {}\n\
+             min_zoom = {}\n\
+             max_zoom = {}\n\
+             line_zoom_sensitivity = {}\n\
+             pixel_zoom_sensitivity = {}\n\
+             click_duration = {}\n\
+             click_distance = {}\n\
+             hold_to_click = {}\n\
+             hold_click_rate = {}\n\
+             hover_collection = {}\n\
+             refund_percent = {}\n\
+             confirm_delete_cost = {}\n",
+            self.edge_scrolling,
+            self.min_zoom,
+            self.max_zoom,
+            self.line_zoom_sensitivity,
+            self.pixel_zoom_sensitivity,
+            self.click_duration,
+            self.click_distance,
+            self.hold_to_click,
+            self.hold_click_rate,
+            self.hover_collection,
+            self.refund_percent,
+            self.confirm_delete_cost,
+        )
+    }
+
+    /// Settings missing from the text keep their default values.
+    pub fn decode(encoded: &str) -> Result<Settings, SettingsDecodeError> {
+        let mut settings = Settings::default();
+
+        for line in encoded
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let invalid_line = || SettingsDecodeError::InvalidLine(line.to_string());
+
+            let (name, value) = line.split_once('=').ok_or_else(invalid_line)?;
+            let value = value.trim();
+
+            match name.trim() {
+                "edge_scrolling" => {
+                    settings.edge_scrolling = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "min_zoom" => settings.min_zoom = value.parse().map_err(|_| invalid_line())?,
+                "max_zoom" => settings.max_zoom = value.parse().map_err(|_| invalid_line())?,
+
+                "line_zoom_sensitivity" => {
+                    settings.line_zoom_sensitivity = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "pixel_zoom_sensitivity" => {
+                    settings.pixel_zoom_sensitivity = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "click_duration" => {
+                    settings.click_duration = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "click_distance" => {
+                    settings.click_distance = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "hold_to_click" => {
+                    settings.hold_to_click = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "hold_click_rate" => {
+                    settings.hold_click_rate = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "hover_collection" => {
+                    settings.hover_collection = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "refund_percent" => {
+                    settings.refund_percent = value.parse().map_err(|_| invalid_line())?
+                }
+
+                "confirm_delete_cost" => {
+                    settings.confirm_delete_cost = value.parse().map_err(|_| invalid_line())?
+                }
+
+                _ => return Err(invalid_line()),
+            }
+        }
+
+        // Clamping panics on an inverted range, NaN fails these comparisons too
+        let valid_zoom_range = settings.min_zoom > 0.0 && settings.min_zoom <= settings.max_zoom;
+
+        if !valid_zoom_range {
+            return Err(SettingsDecodeError::InvalidZoomRange);
+        }
+
+        // Also rejects NaN, a rate of zero would never click again
+        let valid_hold_click_rate = settings.hold_click_rate > 0.0;
+
+        if !valid_hold_click_rate {
+            return Err(SettingsDecodeError::InvalidHoldClickRate);
+        }
+
+        // Refunding more than the machine cost would make coins out of nothing
+        if settings.refund_percent > 100 {
+            return Err(SettingsDecodeError::InvalidRefundPercent);
+        }
+
+        Ok(settings)
+    }
+
+    /// Falls back to the default settings when there is no usable settings file.
+    pub fn load() -> Settings {
+        let encoded = match read_text_file(SETTINGS_FILE) {
+            Ok(encoded) => encoded,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Settings::default(),
+            Err(error) => {
+                warn!("Could not read {}: {}", SETTINGS_FILE, error);
+                return Settings::default();
+            }
+        };
+
+        Settings::decode(&encoded).unwrap_or_else(|error| {
+            warn!("Could not load settings: {:?}", error);
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Err(error) = write_text_file(SETTINGS_FILE, &self.encode()) {
+            warn!("Could not write {}: {}", SETTINGS_FILE, error);
+        }
+    }
+}
+
+/// Settings that can be switched on and off from the controls screen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SettingToggle {
+    EdgeScrolling,
+    HoldToClick,
+    HoverCollection,
+}
+
+impl SettingToggle {
+    pub fn list() -> &'static [SettingToggle] {
+        use SettingToggle::*;
+
+        &[EdgeScrolling, HoldToClick, HoverCollection]
+    }
+
+    pub fn name(&self) -> &str {
+        use SettingToggle::*;
+
+        match self {
+            EdgeScrolling => "Edge scrolling",
+            HoldToClick => "Hold to keep clicking",
+            HoverCollection => "Collect coins on hover",
+        }
+    }
+
+    /// Name of the setting in the settings file.
+    pub fn id(&self) -> &str {
+        use SettingToggle::*;
+
+        match self {
+            EdgeScrolling => "edge_scrolling",
+            HoldToClick => "hold_to_click",
+            HoverCollection => "hover_collection",
+        }
+    }
+
+    pub fn get(&self, settings: &Settings) -> bool {
+        use SettingToggle::*;
+
+        match self {
+            EdgeScrolling => settings.edge_scrolling,
+            HoldToClick => settings.hold_to_click,
+            HoverCollection => settings.hover_collection,
+        }
+    }
+
+    pub fn toggle(&self, settings: &mut Settings) {
+        use SettingToggle::*;
+
+        match self {
+            EdgeScrolling => settings.edge_scrolling = !settings.edge_scrolling,
+            HoldToClick => settings.hold_to_click = !settings.hold_to_click,
+            HoverCollection => settings.hover_collection = !settings.hover_collection,
+        }
+    }
+}
+
+/// Sent when a setting is switched from the controls screen.
+pub struct SettingToggledEvent(pub SettingToggle);
+
+#[derive(Debug)]
+pub enum SettingsDecodeError {
+    InvalidLine(String),
+    InvalidZoomRange,
+    InvalidHoldClickRate,
+    InvalidRefundPercent,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_text_file(path: &str, encoded: &str) -> io::Result<()> {
+    std::fs::write(path, encoded)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_text_file(path: &str) -> io::Result<String> {
+    std::fs::read_to_string(path)
+}
+
+// There is no file system on the web, files written here only last for the session
+#[cfg(target_arch = "wasm32")]
+static SESSION_FILES: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_text_file(path: &str, encoded: &str) -> io::Result<()> {
+    let mut files = SESSION_FILES.lock().unwrap();
+
+    match files.iter_mut().find(|(file_path, _)| file_path == path) {
+        Some((_, contents)) => *contents = encoded.to_string(),
+        None => files.push((path.to_string(), encoded.to_string())),
+    }
+
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read_text_file(path: &str) -> io::Result<String> {
+    SESSION_FILES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(file_path, _)| file_path == path)
+        .map(|(_, contents)| contents.clone())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no file system on the web"))
+}
@@ -1,36 +1,80 @@
 use std::f32::consts::PI;
 use std::time::Duration;
 
-use bevy::math::vec3;
+use bevy::math::{vec2, vec3};
 use bevy::prelude::*;
 use bevy_tweening::lens::{TransformPositionLens, TransformScaleLens};
 use bevy_tweening::*;
+use rand::Rng;
 
 use crate::assets::*;
 use crate::gameplay::components::*;
 use crate::palette;
 
+use super::blueprints::BlueprintClipboard;
+use super::history::EditHistory;
 use super::hud::ToolGhost;
 use super::input::WorldMouseEvent;
+use super::input_map::{Action, InputMap};
+use super::machines::DeleteConfirmation;
+use super::settings::Settings;
 use super::tile_tracked_entities::{TilePosition, TileTrackedEntities, TileTrackedEntity};
+use super::TILE_SIZE;
 
-pub fn startup_gameplay(mut commands: Commands, mut camera: Query<&mut Transform, With<Camera2d>>) {
+const COIN_SPAWN_DURATION: f32 = 0.2;
+
+pub fn startup_gameplay(
+    mut commands: Commands,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+    mut rng: ResMut<GameRng>,
+) {
     camera.single_mut().scale = vec3(4.0, 4.0, 1.0);
 
+    // Every game starts from its own seed, a replay swaps in the recorded one right after
+    *rng = GameRng::from_env();
+
     commands.insert_resource(Balance::default());
 
+    commands.insert_resource(Income::default());
+
     commands.insert_resource(NextCoinDepth {
         depth: 0.1,
         step: 0.00000001,
     });
 
+    commands.insert_resource(CoinLabels::default());
+
+    commands.insert_resource(CoinMerging::default());
+
+    commands.insert_resource(Simulation::default());
+
+    commands.insert_resource(BlueprintClipboard::default());
+
+    commands.insert_resource(EditHistory::default());
+
+    commands.insert_resource(DeleteConfirmation::default());
+
     commands.insert_resource(super::input::WorldMouse::default());
 
+    commands.insert_resource(super::input::TouchPointer::default());
+
+    commands.insert_resource(super::input::CameraZoom::default());
+
     commands.insert_resource(TileTrackedEntities::new());
 }
 
 pub fn move_particles(mut particles: Query<(&mut Transform, &mut Particle)>) {
     for (mut transform, mut particle) in particles.iter_mut() {
+        // Avoid touching transforms of resting particles so they are not re-propagated every frame
+        if particle.velocity == Vec2::ZERO {
+            continue;
+        }
+
+        if particle.velocity.length_squared() < 0.0001 {
+            particle.velocity = Vec2::ZERO;
+            continue;
+        }
+
         transform.translation += particle.velocity.extend(0.0);
         let damping = particle.damping;
         particle.velocity *= damping;
@@ -49,6 +93,8 @@ pub fn spawn_coin(
 ) {
     let font_size = 180.0 / ((value as f32).log10().floor() + 1.0).powf(0.75);
 
+    // Coins are the most numerous entities in the game, so they are kept as lean as possible:
+    // the spawn animation is driven by `update_coins` instead of a per-coin `Animator`.
     commands
         .spawn(SpriteBundle {
             texture: game_images.coin.clone(),
@@ -69,21 +115,13 @@ pub fn spawn_coin(
                 .with_alignment(TextAlignment::CENTER),
                 transform: Transform::from_xyz(0.0, 0.0, depth.step * 0.5),
                 ..default()
-            });
+            })
+            .insert(CoinLabel);
         })
-        .insert(Name::new("Coin"))
         .insert(Particle { velocity, damping })
         .insert(Money(value))
-        .insert(Animator::new(Tween::new(
-            EaseFunction::CubicOut,
-            Duration::from_secs_f32(0.2),
-            TransformScaleLens {
-                start: Vec3::splat(0.0),
-                end: Vec3::splat(1.0),
-            },
-        )))
         .insert(Coin {
-            spawn_timer: Timer::from_seconds(0.2, TimerMode::Once),
+            spawn_timer: Timer::from_seconds(COIN_SPAWN_DURATION, TimerMode::Once),
             despawn_timer: {
                 let mut timer = Timer::from_seconds(0.1, TimerMode::Once);
                 timer.pause();
@@ -100,13 +138,92 @@ pub fn spawn_coin(
     }
 }
 
+/// All coin labels, and the ones spawned since the last frame.
+type CoinLabelVisibilities<'w, 's> = ParamSet<
+    'w,
+    's,
+    (
+        Query<'w, 's, &'static mut Visibility, With<CoinLabel>>,
+        Query<'w, 's, &'static mut Visibility, Added<CoinLabel>>,
+    ),
+>;
+
+/// Hides coin value labels when the camera is zoomed out far enough for them to be unreadable.
+///
+/// Labels are rendered from the font atlas, so interleaving them with coin sprites breaks
+/// sprite batching. With the labels hidden, all coins are drawn in a single batch.
+pub fn update_coin_label_visibility(
+    camera: Query<&Transform, With<Camera2d>>,
+    mut coin_labels: ResMut<CoinLabels>,
+    mut labels: CoinLabelVisibilities,
+) {
+    let visible = camera.single().scale.x <= coin_labels.max_zoom;
+
+    if visible != coin_labels.visible {
+        coin_labels.visible = visible;
+
+        for mut visibility in labels.p0().iter_mut() {
+            visibility.is_visible = visible;
+        }
+    } else if !visible {
+        for mut visibility in labels.p1().iter_mut() {
+            visibility.is_visible = false;
+        }
+    }
+}
+
+/// Fills the area around the origin with coins to measure rendering performance.
+///
+/// Only active in debug builds when `ONE_CLICKER_STRESS_COINS` is set to the number of coins.
+pub fn spawn_stress_scene(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    game_images: Res<Images>,
+    mut depth: ResMut<NextCoinDepth>,
+    mut spawned: Local<bool>,
+) {
+    if !cfg!(debug_assertions) || *spawned {
+        return;
+    }
+
+    *spawned = true;
+
+    let num_coins = match std::env::var("ONE_CLICKER_STRESS_COINS") {
+        Ok(value) => value.parse::<u32>().unwrap_or(0),
+        Err(_) => return,
+    };
+
+    let side = (num_coins as f32).sqrt().ceil() as u32;
+    let spacing = TILE_SIZE / 4.0;
+    let offset = Vec2::splat(side as f32 * spacing / 2.0);
+
+    for i in 0..num_coins {
+        let position = vec2((i % side) as f32, (i / side) as f32) * spacing - offset;
+
+        spawn_coin(
+            &mut commands,
+            &mut depth,
+            &fonts,
+            &game_images,
+            (i % 1000) as Currency + 1,
+            position,
+            Vec2::ZERO,
+            0.0,
+        );
+    }
+
+    info!("Spawned {} coins for the stress scene", num_coins);
+}
+
 pub fn click_coins(
     mut commands: Commands,
     building_ghosts: Query<&ToolGhost>,
     fonts: Res<Fonts>,
     game_images: Res<Images>,
     mut depth: ResMut<NextCoinDepth>,
+    mut rng: ResMut<GameRng>,
     mut world_mouse_events: EventReader<WorldMouseEvent>,
+    input_map: Res<InputMap>,
 ) {
     if !building_ghosts.is_empty() {
         world_mouse_events.clear();
@@ -115,11 +232,10 @@ pub fn click_coins(
 
     for event in world_mouse_events.iter() {
         match event {
-            WorldMouseEvent::Click {
-                button: MouseButton::Left,
-                position,
-            } => {
-                let initial_velocity = Vec2::from_angle(rand::random::<f32>() * 2.0 * PI) * 80.0;
+            WorldMouseEvent::Click { button, position }
+                if input_map.is_button(Action::Place, *button) =>
+            {
+                let initial_velocity = click_velocity(&mut rng);
                 spawn_coin(
                     &mut commands,
                     &mut depth,
@@ -137,11 +253,17 @@ pub fn click_coins(
     }
 }
 
+/// Clicked coins fly off in any direction.
+pub fn click_velocity(rng: &mut GameRng) -> Vec2 {
+    Vec2::from_angle(rng.gen::<f32>() * 2.0 * PI) * 80.0
+}
+
 pub fn update_coins(
     mut commands: Commands,
-    mut coins: Query<(Entity, &Transform, &mut Coin, &Money)>,
+    mut coins: Query<(Entity, &mut Transform, &mut Coin, &Money)>,
     time: Res<Time>,
     mut wallet: ResMut<Balance>,
+    mut income: ResMut<Income>,
     mut coin_pickup_events: EventReader<CoinPickup>,
 ) {
     for event in coin_pickup_events.iter() {
@@ -159,6 +281,7 @@ pub fn update_coins(
             .set_duration(Duration::from_secs_f32(DESPAWN_DURATION));
         coin.despawn_timer.unpause();
         coin.has_money = event.add_money;
+        coin.alive = false;
 
         commands
             .entity(event.coin)
@@ -182,13 +305,21 @@ pub fn update_coins(
             ])));
     }
 
-    for (entity, _, mut coin, coin_money) in coins.iter_mut() {
-        coin.spawn_timer.tick(time.delta());
+    for (entity, mut transform, mut coin, coin_money) in coins.iter_mut() {
+        // The pickup animation scales the coin from here on
+        if coin.alive && !coin.spawn_timer.finished() {
+            coin.spawn_timer.tick(time.delta());
+
+            let t = coin.spawn_timer.percent();
+            transform.scale = Vec3::splat(1.0 - (1.0 - t).powi(3));
+        }
+
         coin.despawn_timer.tick(time.delta());
 
         if coin.despawn_timer.just_finished() {
             if coin.has_money {
                 wallet.coins += coin_money.0;
+                income.credit(time.elapsed_seconds_f64(), coin_money.0);
             }
 
             commands.entity(entity).despawn_recursive();
@@ -196,48 +327,152 @@ pub fn update_coins(
     }
 }
 
+/// Coins are collected under the cursor, or only where the player clicks and drags
+/// when hover collection is turned off.
 pub fn hover_coins(
     coins: Query<(&Transform, &Coin)>,
     mut world_mouse_events: EventReader<WorldMouseEvent>,
     tile_tracked_entities: ResMut<TileTrackedEntities>,
+    settings: Res<Settings>,
+    input_map: Res<InputMap>,
     mut coin_pickup_events: EventWriter<CoinPickup>,
 ) {
     for event in world_mouse_events.iter() {
-        match event {
-            WorldMouseEvent::Hover { position } => {
-                let center_tile = TilePosition::from_world(*position);
-                let tiles_to_check = [
-                    center_tile.offset(-1, -1),
-                    center_tile.offset(0, -1),
-                    center_tile.offset(1, -1),
-                    center_tile.offset(-1, 0),
-                    center_tile,
-                    center_tile.offset(1, 0),
-                    center_tile.offset(-1, 1),
-                    center_tile.offset(0, 1),
-                    center_tile.offset(1, 1),
-                ];
-
-                for tile in tiles_to_check {
-                    if let Some(entities) = tile_tracked_entities.get_entities_in_tile(tile) {
-                        for &entity in entities {
-                            if let Ok((transform, coin)) = coins.get(entity) {
-                                if coin.pickable()
-                                    && position.distance(transform.translation.truncate()) <= 192.0
-                                {
-                                    coin_pickup_events.send(CoinPickup {
-                                        coin: entity,
-                                        target: *position,
-                                        add_money: true,
-                                    });
-                                }
+        let collect_position = match *event {
+            WorldMouseEvent::Hover { position } if settings.hover_collection => Some(position),
+
+            WorldMouseEvent::Click { button, position }
+            | WorldMouseEvent::Drag {
+                button,
+                end_world: position,
+                ..
+            } if !settings.hover_collection && input_map.is_button(Action::Place, button) => {
+                Some(position)
+            }
+
+            _ => None,
+        };
+
+        if let Some(position) = collect_position {
+            let center_tile = TilePosition::from_world(position);
+            let tiles_to_check = [
+                center_tile.offset(-1, -1),
+                center_tile.offset(0, -1),
+                center_tile.offset(1, -1),
+                center_tile.offset(-1, 0),
+                center_tile,
+                center_tile.offset(1, 0),
+                center_tile.offset(-1, 1),
+                center_tile.offset(0, 1),
+                center_tile.offset(1, 1),
+            ];
+
+            for tile in tiles_to_check {
+                if let Some(entities) = tile_tracked_entities.get_entities_in_tile(tile) {
+                    for &entity in entities {
+                        if let Ok((transform, coin)) = coins.get(entity) {
+                            if coin.pickable()
+                                && position.distance(transform.translation.truncate()) <= 192.0
+                            {
+                                coin_pickup_events.send(CoinPickup {
+                                    coin: entity,
+                                    target: position,
+                                    add_money: true,
+                                });
                             }
                         }
                     }
                 }
             }
+        }
+    }
+}
 
-            _ => (),
+pub fn simulation_running(simulation: Res<Simulation>) -> bool {
+    !simulation.paused
+}
+
+pub fn toggle_coin_merging(
+    keys: Res<Input<KeyCode>>,
+    input_map: Res<InputMap>,
+    mut coin_merging: ResMut<CoinMerging>,
+) {
+    if input_map.just_pressed(Action::ToggleMerging, &keys) {
+        coin_merging.enabled = !coin_merging.enabled;
+    }
+}
+
+pub fn merge_coins(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    game_images: Res<Images>,
+    mut depth: ResMut<NextCoinDepth>,
+    mut coin_merging: ResMut<CoinMerging>,
+    mut coins: Query<(&Transform, &mut Coin, &Money, &Particle)>,
+    tile_tracked_entities: Res<TileTrackedEntities>,
+    time: Res<Time>,
+    mut coin_pickup_events: EventWriter<CoinPickup>,
+) {
+    if !coin_merging.enabled {
+        return;
+    }
+
+    coin_merging.timer.tick(time.delta());
+
+    if !coin_merging.timer.just_finished() {
+        return;
+    }
+
+    let mut merged_coins = Vec::new();
+
+    // The tiles are stored in a hash map, spawning the merged coins in a fixed order
+    // keeps replays of the same seed alike
+    let mut tiles = tile_tracked_entities.iter().collect::<Vec<_>>();
+    tiles.sort_by_key(|(position, _)| (position.x, position.y));
+
+    for (_, entities) in tiles {
+        merged_coins.clear();
+
+        for &entity in entities {
+            if let Ok((transform, coin, money, particle)) = coins.get(entity) {
+                // Coins still flying off a machine are left alone until they settle
+                if coin.pickable() && particle.velocity == Vec2::ZERO {
+                    merged_coins.push((entity, transform.translation.truncate(), money.0));
+                }
+            }
+        }
+
+        if merged_coins.len() < 2 {
+            continue;
         }
+
+        let center = merged_coins
+            .iter()
+            .map(|&(_, position, _)| position)
+            .sum::<Vec2>()
+            / merged_coins.len() as f32;
+        let value = merged_coins.iter().map(|&(_, _, value)| value).sum();
+
+        for &(entity, _, _) in merged_coins.iter() {
+            let (_, mut coin, _, _) = coins.get_mut(entity).unwrap();
+            coin.alive = false;
+
+            coin_pickup_events.send(CoinPickup {
+                coin: entity,
+                target: center,
+                add_money: false,
+            });
+        }
+
+        spawn_coin(
+            &mut commands,
+            &mut depth,
+            &fonts,
+            &game_images,
+            value,
+            center,
+            Vec2::ZERO,
+            0.0,
+        );
     }
 }
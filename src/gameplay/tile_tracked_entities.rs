@@ -59,6 +59,54 @@ impl TilePosition {
     pub fn snap_world(position: Vec2) -> Vec2 {
         TilePosition::from_world(position).to_world()
     }
+
+    /// Tiles on the line between the two positions, including both ends.
+    pub fn line_to(&self, end: TilePosition) -> Vec<TilePosition> {
+        let dx = (end.x - self.x).abs();
+        let dy = -(end.y - self.y).abs();
+        let step_x = if self.x < end.x { 1 } else { -1 };
+        let step_y = if self.y < end.y { 1 } else { -1 };
+
+        let mut error = dx + dy;
+        let mut current = *self;
+        let mut tiles = vec![current];
+
+        while current != end {
+            let doubled_error = 2 * error;
+
+            if doubled_error >= dy {
+                error += dy;
+                current.x += step_x;
+            }
+
+            if doubled_error <= dx {
+                error += dx;
+                current.y += step_y;
+            }
+
+            tiles.push(current);
+        }
+
+        tiles
+    }
+
+    /// Tiles on the L-shaped path between the two positions, including both ends.
+    /// The path goes along the longer axis first.
+    pub fn l_path_to(&self, end: TilePosition) -> Vec<TilePosition> {
+        let dx = end.x - self.x;
+        let dy = end.y - self.y;
+
+        let corner = if dx.abs() >= dy.abs() {
+            TilePosition::new(end.x, self.y)
+        } else {
+            TilePosition::new(self.x, end.y)
+        };
+
+        let mut tiles = self.line_to(corner);
+        tiles.extend(corner.line_to(end).into_iter().skip(1));
+
+        tiles
+    }
 }
 
 #[derive(Resource)]
@@ -91,6 +139,10 @@ impl TileTrackedEntities {
         self.map.get(&tile_pos)
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (&TilePosition, &Vec<Entity>)> {
+        self.map.iter()
+    }
+
     pub fn get_entities_in_tile_mut(&mut self, tile_pos: TilePosition) -> Option<&mut Vec<Entity>> {
         self.map.get_mut(&tile_pos)
     }
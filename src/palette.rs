@@ -6,3 +6,11 @@ pub const BLUE: Color = Color::rgb(0.176, 0.38, 0.639);
 pub const DARK_BLUE: Color = Color::rgb(0.188, 0.208, 0.278);
 pub const LIGHT_BROWN: Color = Color::rgb(0.612, 0.529, 0.463);
 pub const ORANGE: Color = Color::rgb(0.863, 0.612, 0.098);
+pub const GREEN: Color = Color::rgb(0.365, 0.631, 0.4);
+pub const PURPLE: Color = Color::rgb(0.529, 0.388, 0.702);
+
+pub const VALID_GHOST: Color = Color::rgba(1.0, 1.0, 1.0, 0.75);
+pub const INVALID_GHOST: Color = Color::rgba(0.863, 0.3, 0.25, 0.75);
+pub const SELECTION: Color = Color::rgba(0.176, 0.38, 0.639, 0.25);
+pub const SELECTED: Color = Color::rgb(0.6, 0.78, 1.0);
+pub const DISABLED: Color = Color::rgba(0.5, 0.5, 0.5, 0.6);